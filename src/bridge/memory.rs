@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use arcstr::ArcStr;
+use async_trait::async_trait;
+use color_eyre::eyre;
+use mesagisto_client::data::message::Message;
+use tokio::sync::broadcast;
+
+use super::{
+  mesagisto::{chat_message, notice_message, to_remote, Published},
+  Bridge,
+};
+use crate::data::{ChatMessage, RemoteMessage};
+
+/// A Mesagisto channel kept in memory. As with NATS, everything published to
+/// it goes to every bridge on it, the publisher included.
+#[derive(Clone)]
+pub struct MemoryChannel {
+  name: ArcStr,
+  messages: broadcast::Sender<Arc<Message>>,
+}
+
+impl MemoryChannel {
+  pub fn new(name: ArcStr) -> Self {
+    let (messages, _) = broadcast::channel(64);
+    Self { name, messages }
+  }
+}
+
+/// Stands in for [super::MesagistoBridge] in tests, with the same messages and
+/// the same handling of its own ones.
+pub struct MemoryBridge {
+  name: ArcStr,
+  channel: MemoryChannel,
+  incoming: broadcast::Sender<RemoteMessage>,
+  published: Arc<Published>,
+}
+
+impl MemoryBridge {
+  pub fn new(name: ArcStr, channel: &MemoryChannel) -> Self {
+    let (incoming, _) = broadcast::channel(64);
+    let bridge = Self {
+      name,
+      channel: channel.clone(),
+      incoming,
+      published: Default::default(),
+    };
+    let mut messages = channel.messages.subscribe();
+    let incoming = bridge.incoming.clone();
+    let published = bridge.published.clone();
+    let channel = channel.name.clone();
+    tokio::spawn(async move {
+      while let Ok(message) = messages.recv().await {
+        if !published.contains(&message.id) {
+          let _ = incoming.send(to_remote(&message, None, &channel));
+        }
+      }
+    });
+    bridge
+  }
+
+  fn send(&self, message: Message) -> eyre::Result<()> {
+    self.published.remember(message.id.clone());
+    self.channel.messages.send(Arc::new(message))?;
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl Bridge for MemoryBridge {
+  async fn publish(&self, message: ChatMessage) -> eyre::Result<()> {
+    self.send(chat_message(message))
+  }
+
+  async fn notify(&self, text: String) -> eyre::Result<()> {
+    self.send(notice_message(&self.name, text))
+  }
+
+  fn subscribe(&self) -> broadcast::Receiver<RemoteMessage> {
+    self.incoming.subscribe()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::Sender;
+
+  fn said(name: &str, content: &str) -> ChatMessage {
+    let sender = Sender {
+      name: name.to_owned(),
+      uuid: None,
+      prefix: None,
+    };
+    ChatMessage::new(sender, content.to_owned())
+  }
+
+  #[tokio::test]
+  async fn relays_between_bridges_but_not_back() {
+    let channel = MemoryChannel::new(arcstr::literal!("survival"));
+    let bot = MemoryBridge::new(arcstr::literal!("bot"), &channel);
+    let other = MemoryBridge::new(arcstr::literal!("other"), &channel);
+    let mut bot_incoming = bot.subscribe();
+    let mut other_incoming = other.subscribe();
+
+    bot.publish(said("Steve", "hello")).await.unwrap();
+    let message = other_incoming.recv().await.unwrap();
+    assert_eq!(message.sender, "Steve");
+    assert_eq!(message.content, "hello");
+    assert_eq!(message.channel, "survival");
+
+    // messages in the same millisecond still have ids of their own
+    bot.notify(String::from("Alex joined the game")).await.unwrap();
+    bot.notify(String::from("Alex left the game")).await.unwrap();
+    assert_eq!(other_incoming.recv().await.unwrap().content, "Alex joined the game");
+    assert_eq!(other_incoming.recv().await.unwrap().content, "Alex left the game");
+
+    // the bot only gets what the other side said, after everything it said
+    other.publish(said("Herobrine", "hi")).await.unwrap();
+    let message = bot_incoming.recv().await.unwrap();
+    assert_eq!(message.sender, "Herobrine");
+    assert_eq!(message.content, "hi");
+    assert!(bot_incoming.try_recv().is_err());
+  }
+
+  #[tokio::test]
+  async fn shows_the_prefix_in_the_nick() {
+    let channel = MemoryChannel::new(arcstr::literal!("survival"));
+    let bot = MemoryBridge::new(arcstr::literal!("bot"), &channel);
+    let other = MemoryBridge::new(arcstr::literal!("other"), &channel);
+    let mut other_incoming = other.subscribe();
+
+    let mut message = said("Steve", "hello");
+    message.sender.prefix = Some(String::from("Admin"));
+    bot.publish(message).await.unwrap();
+    assert_eq!(other_incoming.recv().await.unwrap().sender, "[Admin] Steve");
+  }
+}
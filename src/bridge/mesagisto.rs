@@ -0,0 +1,218 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+};
+
+use arcstr::ArcStr;
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::eyre;
+use mesagisto_client::{
+  data::{
+    message::{Message, MessageType, Profile},
+    Packet,
+  },
+  server::SERVER,
+};
+use tokio::sync::broadcast;
+use tracing::{debug, info, trace, warn};
+
+use super::{Bridge, TARGET};
+use crate::{
+  config::MesagistoConfig,
+  data::{ChatMessage, RemoteMessage},
+};
+
+// remote messages waiting for a slow connection, older ones are skipped
+const INCOMING_CAPACITY: usize = 64;
+// ids of our own messages kept to recognise them when NATS echoes them back
+const PUBLISHED_IDS: usize = 64;
+// the NATS header in which clients may tell the platform a message comes from
+const PLATFORM_HEADER: &str = "platform";
+
+/// Publishes to and receives from one Mesagisto channel through
+/// `mesagisto-client`.
+pub struct MesagistoBridge {
+  // notices are sent under this name
+  name: ArcStr,
+  channel: ArcStr,
+  incoming: broadcast::Sender<RemoteMessage>,
+  published: Arc<Published>,
+}
+
+/// The ids of the messages a bridge published last, to recognise them when
+/// the channel delivers them back.
+#[derive(Debug, Default)]
+pub(super) struct Published(Mutex<VecDeque<Vec<u8>>>);
+
+impl Published {
+  pub fn remember(&self, id: Vec<u8>) {
+    let mut published = self.0.lock().unwrap();
+    if published.len() == PUBLISHED_IDS {
+      published.pop_front();
+    }
+    published.push_back(id);
+  }
+
+  pub fn contains(&self, id: &[u8]) -> bool {
+    self.0.lock().unwrap().iter().any(|published| published == id)
+  }
+}
+
+/// Connects `mesagisto-client` to the NATS server and sets up the cipher.
+/// Must be called once, before any [MesagistoBridge] is created.
+pub async fn init(config: &MesagistoConfig) -> eyre::Result<()> {
+  mesagisto_client::MesagistoConfig::builder()
+    .name("mc")
+    .cipher_key(config.cipher_key.clone())
+    .nats_address(config.nats.clone())
+    .build()
+    .apply()
+    .await?;
+  info!(target: TARGET, "Connected to NATS server {}", config.nats);
+  Ok(())
+}
+
+impl MesagistoBridge {
+  /// Subscribes to `channel`. Notices are published as said by `name`.
+  pub async fn new(name: ArcStr, channel: ArcStr) -> eyre::Result<Self> {
+    let (incoming, _) = broadcast::channel(INCOMING_CAPACITY);
+    let bridge = Self {
+      name,
+      channel,
+      incoming,
+      published: Default::default(),
+    };
+    let incoming = bridge.incoming.clone();
+    let published = bridge.published.clone();
+    let channel = bridge.channel.clone();
+    SERVER
+      .recv(bridge.channel.clone(), move |message: nats::Message| {
+        let incoming = incoming.clone();
+        let published = published.clone();
+        let channel = channel.clone();
+        async move {
+          let platform = message
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get(PLATFORM_HEADER))
+            .and_then(|platform| platform.to_str().ok())
+            .map(str::to_owned);
+          let message = match Packet::from_cbor(&message.payload)?.left() {
+            Some(message) => message,
+            // events carry nothing to show in game
+            None => return Ok(()),
+          };
+          if published.contains(&message.id) {
+            trace!(target: TARGET, "Skipping our own message {:?}", message.id);
+            return Ok(());
+          }
+          // nobody listens while the bot is disconnected
+          let _ = incoming.send(to_remote(&message, platform, &channel));
+          Ok(())
+        }
+      })
+      .await?;
+    info!(target: TARGET, "Subscribed to channel {}", bridge.channel);
+    Ok(bridge)
+  }
+}
+
+pub(super) fn to_remote(
+  message: &Message,
+  platform: Option<String>,
+  channel: &str,
+) -> RemoteMessage {
+  let sender = message
+    .profile
+    .nick
+    .clone()
+    .or_else(|| message.profile.username.clone())
+    .unwrap_or_else(|| String::from("?"));
+  let content = message
+    .chain
+    .iter()
+    .map(|item| match item {
+      MessageType::Text { content } => content.clone(),
+      _ => {
+        warn!(target: TARGET, "Only text messages can be shown in game");
+        String::from("[unsupported]")
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(" ");
+  RemoteMessage {
+    sender,
+    content,
+    platform,
+    channel: channel.to_owned(),
+    timestamp: Utc::now(),
+  }
+}
+
+/// The message publishing `message`, with the player as its profile.
+pub(super) fn chat_message(message: ChatMessage) -> Message {
+  let profile = Profile {
+    // offline servers may not tell the uuid, the name is unique anyway
+    id: match message.sender.uuid {
+      Some(uuid) => uuid.as_bytes().to_vec(),
+      None => message.sender.name.as_bytes().to_vec(),
+    },
+    nick: message
+      .sender
+      .prefix
+      .map(|prefix| format!("[{}] {}", prefix, message.sender.name)),
+    username: Some(message.sender.name),
+  };
+  text_message(profile, message.content)
+}
+
+/// The message publishing `text` as said by the binding `name` itself.
+pub(super) fn notice_message(name: &str, text: String) -> Message {
+  let profile = Profile {
+    id: name.as_bytes().to_vec(),
+    username: Some(name.to_owned()),
+    nick: None,
+  };
+  text_message(profile, text)
+}
+
+fn text_message(profile: Profile, content: String) -> Message {
+  Message {
+    profile,
+    // random, two messages said in the same millisecond must not share an id
+    id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+    reply: None,
+    chain: vec![MessageType::Text { content }],
+  }
+}
+
+#[async_trait]
+impl Bridge for MesagistoBridge {
+  async fn publish(&self, message: ChatMessage) -> eyre::Result<()> {
+    // Mesagisto messages have no time of their own
+    trace!(
+      target: TARGET,
+      "Chat of {} said at {}", message.sender.name, message.timestamp
+    );
+    self.send(chat_message(message)).await
+  }
+
+  async fn notify(&self, text: String) -> eyre::Result<()> {
+    self.send(notice_message(&self.name, text)).await
+  }
+
+  fn subscribe(&self) -> broadcast::Receiver<RemoteMessage> {
+    self.incoming.subscribe()
+  }
+}
+
+impl MesagistoBridge {
+  async fn send(&self, message: Message) -> eyre::Result<()> {
+    debug!(target: TARGET, "Publishing to {}: {:?}", self.channel, message);
+    self.published.remember(message.id.clone());
+    let packet = Packet::from(message.tl())?;
+    SERVER.send(&self.channel, packet).await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,28 @@
+pub mod mesagisto;
+#[cfg(test)]
+pub mod memory;
+
+use async_trait::async_trait;
+use color_eyre::eyre;
+use tokio::sync::broadcast;
+
+use crate::data::{ChatMessage, RemoteMessage};
+
+pub use self::mesagisto::MesagistoBridge;
+
+const TARGET: &str = "mesagisto::bridge";
+
+/// The remote side of the bridge. Handlers only talk to this trait, so a
+/// connection can be driven against an in-process stand-in instead of NATS.
+#[async_trait]
+pub trait Bridge: Send + Sync {
+  async fn publish(&self, message: ChatMessage) -> eyre::Result<()>;
+
+  /// Publishes `text` as said by the server itself, for lines without a
+  /// player behind them.
+  async fn notify(&self, text: String) -> eyre::Result<()>;
+
+  /// Messages from the other platforms of the channel, starting now. The
+  /// ones this bot published itself are left out.
+  fn subscribe(&self) -> broadcast::Receiver<RemoteMessage>;
+}
@@ -0,0 +1,457 @@
+use serde_json::{json, Map, Value};
+
+use super::Lang;
+
+/// A chat component with everything the client renders, unlike
+/// `steven_protocol::format::Component` which only keeps text.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Component {
+  pub content: Content,
+  pub style: Style,
+  pub extra: Vec<Component>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+  Text(String),
+  Translate { key: String, with: Vec<Component> },
+  // `value` is filled in by the server before sending
+  Score { name: String, objective: String, value: Option<String> },
+  Selector(String),
+  Keybind(String),
+}
+
+impl Default for Content {
+  fn default() -> Self {
+    Content::Text(String::new())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Style {
+  pub color: Option<String>,
+  pub bold: Option<bool>,
+  pub italic: Option<bool>,
+  pub underlined: Option<bool>,
+  pub strikethrough: Option<bool>,
+  pub obfuscated: Option<bool>,
+  // vanilla puts the plain player name here for the sender of a chat line
+  pub insertion: Option<String>,
+  pub click: Option<ClickEvent>,
+  // the text shown on hover, other hover events are not kept
+  pub hover: Option<Box<Component>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClickEvent {
+  // such as open_url, run_command or copy_to_clipboard
+  pub action: String,
+  pub value: String,
+}
+
+/// A chat line with who said it kept apart from what was said.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatLine {
+  /// The translation key the line was built from, if any.
+  pub key: Option<String>,
+  pub sender: Option<String>,
+  pub message: String,
+  /// The message with its styles, for vanilla formats.
+  pub styled: Option<Component>,
+}
+
+// chat formats and the positions of their sender and message arguments
+const CHAT_FORMATS: &[(&str, usize, usize)] = &[
+  ("chat.type.text", 0, 1),
+  ("chat.type.emote", 0, 1),
+  ("chat.type.announcement", 0, 1),
+  ("chat.type.team.text", 1, 2),
+  ("commands.message.display.incoming", 0, 1),
+];
+
+impl Component {
+  pub fn text(text: impl Into<String>) -> Self {
+    Component {
+      content: Content::Text(text.into()),
+      ..Default::default()
+    }
+  }
+
+  /// Parses the JSON form used on the wire. Never fails, parts which are not
+  /// understood render as nothing.
+  pub fn from_json(value: &Value) -> Self {
+    match value {
+      Value::String(text) => Component::text(text.as_str()),
+      Value::Bool(_) | Value::Number(_) => Component::text(value.to_string()),
+      // the first element is the parent of the others
+      Value::Array(items) => {
+        let mut items = items.iter().map(Component::from_json);
+        let mut component = items.next().unwrap_or_default();
+        component.extra.extend(items);
+        component
+      }
+      Value::Object(object) => {
+        let str_of = |value: &Value| value.as_str().map(str::to_owned);
+        let content = if let Some(text) = object.get("text") {
+          Content::Text(match text {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+          })
+        } else if let Some(key) = object.get("translate").and_then(Value::as_str) {
+          Content::Translate {
+            key: key.to_owned(),
+            with: match object.get("with") {
+              Some(Value::Array(args)) => args.iter().map(Component::from_json).collect(),
+              _ => Vec::new(),
+            },
+          }
+        } else if let Some(score) = object.get("score") {
+          Content::Score {
+            name: score.get("name").and_then(str_of).unwrap_or_default(),
+            objective: score.get("objective").and_then(str_of).unwrap_or_default(),
+            value: score.get("value").map(|value| match value {
+              Value::String(value) => value.clone(),
+              other => other.to_string(),
+            }),
+          }
+        } else if let Some(selector) = object.get("selector").and_then(str_of) {
+          Content::Selector(selector)
+        } else if let Some(keybind) = object.get("keybind").and_then(str_of) {
+          Content::Keybind(keybind)
+        } else {
+          Content::default()
+        };
+        let flag = |name: &str| object.get(name).and_then(Value::as_bool);
+        let style = Style {
+          color: object.get("color").and_then(str_of),
+          bold: flag("bold"),
+          italic: flag("italic"),
+          underlined: flag("underlined"),
+          strikethrough: flag("strikethrough"),
+          obfuscated: flag("obfuscated"),
+          insertion: object.get("insertion").and_then(str_of),
+          click: object.get("clickEvent").and_then(|click| {
+            Some(ClickEvent {
+              action: click.get("action").and_then(str_of)?,
+              value: click.get("value").and_then(str_of)?,
+            })
+          }),
+          hover: object
+            .get("hoverEvent")
+            .filter(|hover| hover.get("action").and_then(Value::as_str) == Some("show_text"))
+            // `value` until 1.16, `contents` since
+            .and_then(|hover| hover.get("contents").or_else(|| hover.get("value")))
+            .map(|text| Box::new(Component::from_json(text))),
+        };
+        let extra = match object.get("extra") {
+          Some(Value::Array(extra)) => extra.iter().map(Component::from_json).collect(),
+          _ => Vec::new(),
+        };
+        Component {
+          content,
+          style,
+          extra,
+        }
+      }
+      Value::Null => Component::default(),
+    }
+  }
+
+  /// The JSON form used on the wire, as compact as possible since commands
+  /// carrying it share the length limit of chat.
+  pub fn to_json(&self) -> Value {
+    if self.style == Style::default() && self.extra.is_empty() {
+      if let Content::Text(text) = &self.content {
+        return Value::String(text.clone());
+      }
+    }
+    let mut object = Map::new();
+    match &self.content {
+      Content::Text(text) => {
+        object.insert("text".into(), text.as_str().into());
+      }
+      Content::Translate { key, with } => {
+        object.insert("translate".into(), key.as_str().into());
+        if !with.is_empty() {
+          object.insert("with".into(), with.iter().map(Component::to_json).collect());
+        }
+      }
+      Content::Score {
+        name,
+        objective,
+        value,
+      } => {
+        let mut score = json!({ "name": name, "objective": objective });
+        if let Some(value) = value {
+          score["value"] = value.as_str().into();
+        }
+        object.insert("score".into(), score);
+      }
+      Content::Selector(selector) => {
+        object.insert("selector".into(), selector.as_str().into());
+      }
+      Content::Keybind(keybind) => {
+        object.insert("keybind".into(), keybind.as_str().into());
+      }
+    }
+    let style = &self.style;
+    if let Some(color) = &style.color {
+      object.insert("color".into(), color.as_str().into());
+    }
+    for (name, flag) in [
+      ("bold", style.bold),
+      ("italic", style.italic),
+      ("underlined", style.underlined),
+      ("strikethrough", style.strikethrough),
+      ("obfuscated", style.obfuscated),
+    ] {
+      if let Some(flag) = flag {
+        object.insert(name.into(), flag.into());
+      }
+    }
+    if let Some(insertion) = &style.insertion {
+      object.insert("insertion".into(), insertion.as_str().into());
+    }
+    if let Some(click) = &style.click {
+      object.insert(
+        "clickEvent".into(),
+        json!({ "action": click.action, "value": click.value }),
+      );
+    }
+    if let Some(hover) = &style.hover {
+      // `value` is still understood by 1.20
+      object.insert(
+        "hoverEvent".into(),
+        json!({ "action": "show_text", "value": hover.to_json() }),
+      );
+    }
+    if !self.extra.is_empty() {
+      object.insert("extra".into(), self.extra.iter().map(Component::to_json).collect());
+    }
+    Value::Object(object)
+  }
+
+  /// The text as a player reads it in game, without any formatting.
+  pub fn to_plain(&self, lang: &Lang) -> String {
+    let mut out = String::new();
+    self.push_plain(lang, &mut out);
+    out
+  }
+
+  fn push_plain(&self, lang: &Lang, out: &mut String) {
+    self.push_content(lang, out);
+    for child in &self.extra {
+      child.push_plain(lang, out);
+    }
+  }
+
+  // the text of this component alone, without its children
+  pub(super) fn push_content(&self, lang: &Lang, out: &mut String) {
+    match &self.content {
+      Content::Text(text) => out.push_str(text),
+      Content::Translate { key, with } => {
+        let args: Vec<String> = with.iter().map(|arg| arg.to_plain(lang)).collect();
+        out.push_str(&lang.translate(key, &args));
+      }
+      Content::Score { value, .. } => out.push_str(value.as_deref().unwrap_or_default()),
+      // the server resolves selectors, one left over is shown as written
+      Content::Selector(selector) => out.push_str(selector),
+      Content::Keybind(keybind) => out.push_str(default_key(keybind)),
+    }
+  }
+
+  /// Splits vanilla chat formats into sender and message. Anything else comes
+  /// back whole as the message, without a sender.
+  pub fn to_chat_line(&self, lang: &Lang) -> ChatLine {
+    if let Content::Translate { key, with } = &self.content {
+      let format = CHAT_FORMATS.iter().find(|(format, ..)| format == key);
+      if let Some((_, sender, message)) = format {
+        if let (Some(sender), Some(message)) = (with.get(*sender), with.get(*message)) {
+          let name = match &sender.style.insertion {
+            Some(name) => name.clone(),
+            None => sender.to_plain(lang),
+          };
+          let mut styled = message.clone();
+          styled.extra.extend(self.extra.iter().cloned());
+          return ChatLine {
+            key: Some(key.clone()),
+            sender: Some(name),
+            message: styled.to_plain(lang),
+            styled: Some(styled),
+          };
+        }
+      }
+    }
+    ChatLine {
+      key: match &self.content {
+        Content::Translate { key, .. } => Some(key.clone()),
+        _ => None,
+      },
+      sender: None,
+      message: self.to_plain(lang),
+      styled: None,
+    }
+  }
+}
+
+// what the client shows for a keybind nobody has changed
+fn default_key(keybind: &str) -> &str {
+  match keybind {
+    "key.forward" => "W",
+    "key.left" => "A",
+    "key.back" => "S",
+    "key.right" => "D",
+    "key.jump" => "Space",
+    "key.sneak" => "Left Shift",
+    "key.sprint" => "Left Control",
+    "key.inventory" => "E",
+    "key.swapOffhand" => "F",
+    "key.drop" => "Q",
+    "key.use" => "Right Button",
+    "key.attack" => "Left Button",
+    "key.pickItem" => "Middle Button",
+    "key.chat" => "T",
+    "key.playerlist" => "Tab",
+    "key.command" => "/",
+    "key.screenshot" => "F2",
+    "key.togglePerspective" => "F5",
+    "key.fullscreen" => "F11",
+    "key.advancements" => "L",
+    other => match other.strip_prefix("key.hotbar.") {
+      Some(slot) => slot,
+      None => other,
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(json: Value) -> Component {
+    Component::from_json(&json)
+  }
+
+  fn plain(json: Value) -> String {
+    parse(json).to_plain(&Lang::load("en_us").unwrap())
+  }
+
+  fn line(json: Value) -> ChatLine {
+    parse(json).to_chat_line(&Lang::load("en_us").unwrap())
+  }
+
+  #[test]
+  fn parses_each_content() {
+    assert_eq!(parse(json!("hi")), Component::text("hi"));
+    assert_eq!(parse(json!({ "text": 42 })).content, Content::Text(String::from("42")));
+    assert_eq!(
+      parse(json!({ "score": { "name": "Steve", "objective": "kills", "value": "3" } })).content,
+      Content::Score {
+        name: String::from("Steve"),
+        objective: String::from("kills"),
+        value: Some(String::from("3")),
+      }
+    );
+    assert_eq!(
+      parse(json!({ "selector": "@p" })).content,
+      Content::Selector(String::from("@p"))
+    );
+    assert_eq!(
+      parse(json!({ "keybind": "key.jump" })).content,
+      Content::Keybind(String::from("key.jump"))
+    );
+    let styled = parse(json!({
+      "text": "x",
+      "color": "red",
+      "bold": true,
+      "insertion": "Steve",
+      "clickEvent": { "action": "suggest_command", "value": "/msg Steve " },
+      "hoverEvent": { "action": "show_text", "contents": "hover" },
+    }));
+    assert_eq!(styled.style.color.as_deref(), Some("red"));
+    assert_eq!(styled.style.bold, Some(true));
+    assert_eq!(styled.style.italic, None);
+    assert_eq!(styled.style.insertion.as_deref(), Some("Steve"));
+    assert_eq!(styled.style.click.unwrap().action, "suggest_command");
+    assert_eq!(styled.style.hover, Some(Box::new(Component::text("hover"))));
+  }
+
+  #[test]
+  fn renders_each_content() {
+    assert_eq!(plain(json!({ "text": "plain" })), "plain");
+    assert_eq!(
+      plain(json!({ "score": { "name": "Steve", "objective": "kills", "value": "3" } })),
+      "3"
+    );
+    assert_eq!(plain(json!({ "score": { "name": "Steve", "objective": "kills" } })), "");
+    assert_eq!(plain(json!({ "selector": "@p" })), "@p");
+    assert_eq!(plain(json!({ "keybind": "key.jump" })), "Space");
+    assert_eq!(plain(json!({ "keybind": "key.hotbar.3" })), "3");
+    assert_eq!(plain(json!(null)), "");
+  }
+
+  #[test]
+  fn renders_translations_with_component_args() {
+    let death = json!({
+      "translate": "death.attack.arrow",
+      "with": [
+        { "text": "Steve", "extra": [{ "text": "!" }] },
+        { "translate": "entity.minecraft.skeleton", "color": "red" },
+      ],
+    });
+    assert_eq!(plain(death), "Steve! was shot by Skeleton");
+  }
+
+  #[test]
+  fn renders_arrays_and_nested_extra() {
+    // the first element is the parent of the others
+    let array = json!(["a", { "text": "b", "extra": ["c", { "text": "d", "extra": ["e"] }] }, "f"]);
+    let component = parse(array.clone());
+    assert_eq!(component.extra.len(), 2);
+    assert_eq!(plain(array), "abcdef");
+  }
+
+  #[test]
+  fn splits_chat_formats() {
+    let text = line(json!({
+      "translate": "chat.type.text",
+      "with": [{ "text": "Steve", "insertion": "Steve" }, "hello"],
+    }));
+    assert_eq!(text.key.as_deref(), Some("chat.type.text"));
+    assert_eq!(text.sender.as_deref(), Some("Steve"));
+    assert_eq!(text.message, "hello");
+
+    let announcement =
+      line(json!({ "translate": "chat.type.announcement", "with": ["Server", "hi"] }));
+    assert_eq!(announcement.sender.as_deref(), Some("Server"));
+    assert_eq!(announcement.message, "hi");
+
+    let emote = line(json!({ "translate": "chat.type.emote", "with": ["Steve", "waves"] }));
+    assert_eq!(emote.sender.as_deref(), Some("Steve"));
+    assert_eq!(emote.message, "waves");
+
+    // the name shown with a team prefix, the plain one in the insertion
+    let team = line(json!({
+      "translate": "chat.type.team.text",
+      "with": [
+        "Red",
+        { "text": "", "insertion": "Steve", "extra": ["[Red] ", "Steve"] },
+        { "text": "go", "bold": true },
+      ],
+    }));
+    assert_eq!(team.sender.as_deref(), Some("Steve"));
+    assert_eq!(team.message, "go");
+    assert_eq!(team.styled.unwrap().style.bold, Some(true));
+  }
+
+  #[test]
+  fn keeps_other_lines_whole() {
+    let joined = line(json!({ "translate": "multiplayer.player.joined", "with": ["Steve"] }));
+    assert_eq!(joined.key.as_deref(), Some("multiplayer.player.joined"));
+    assert_eq!(joined.sender, None);
+    assert_eq!(joined.message, "Steve joined the game");
+    let plugin = line(json!("[Admin] Steve: hi"));
+    assert_eq!((plugin.key, plugin.sender), (None, None));
+    assert_eq!(plugin.message, "[Admin] Steve: hi");
+  }
+}
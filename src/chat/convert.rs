@@ -0,0 +1,493 @@
+use super::{
+  component::{Content, Style},
+  Component, Lang,
+};
+
+// Conversions between chat components and the formatting of other places:
+// Markdown on the platforms Mesagisto bridges to, and the legacy formatting
+// codes which servers and chat plugins still understand.
+
+/// A style Markdown can express. Colors have no Markdown equivalent and are
+/// dropped on the way there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+  Bold,
+  Underlined,
+  Italic,
+  Strikethrough,
+  // a spoiler hides text much like obfuscated text does
+  Obfuscated,
+}
+
+impl Mark {
+  // the order in which markers are opened, outermost first
+  const ALL: [Mark; 5] = [
+    Mark::Bold,
+    Mark::Underlined,
+    Mark::Italic,
+    Mark::Strikethrough,
+    Mark::Obfuscated,
+  ];
+
+  // Discord flavoured, which the other platforms mostly accept as well
+  fn marker(self) -> &'static str {
+    match self {
+      Mark::Bold => "**",
+      Mark::Underlined => "__",
+      Mark::Italic => "_",
+      Mark::Strikethrough => "~~",
+      Mark::Obfuscated => "||",
+    }
+  }
+
+  fn is_set(self, style: &Style) -> bool {
+    let flag = match self {
+      Mark::Bold => style.bold,
+      Mark::Underlined => style.underlined,
+      Mark::Italic => style.italic,
+      Mark::Strikethrough => style.strikethrough,
+      Mark::Obfuscated => style.obfuscated,
+    };
+    flag.unwrap_or(false)
+  }
+
+  fn set(self, style: &mut Style) {
+    let flag = match self {
+      Mark::Bold => &mut style.bold,
+      Mark::Underlined => &mut style.underlined,
+      Mark::Italic => &mut style.italic,
+      Mark::Strikethrough => &mut style.strikethrough,
+      Mark::Obfuscated => &mut style.obfuscated,
+    };
+    *flag = Some(true);
+  }
+}
+
+/// A piece of text with the style it is shown in, inherited from its parents.
+struct Run {
+  text: String,
+  style: Style,
+}
+
+fn marks(style: &Style) -> Vec<Mark> {
+  Mark::ALL
+    .into_iter()
+    .filter(|mark| mark.is_set(style))
+    .collect()
+}
+
+fn runs(component: &Component, lang: &Lang) -> Vec<Run> {
+  let mut runs = Vec::new();
+  push_runs(component, lang, &Style::default(), &mut runs);
+  runs
+}
+
+fn push_runs(component: &Component, lang: &Lang, parent: &Style, runs: &mut Vec<Run>) {
+  let own = &component.style;
+  let style = Style {
+    color: own.color.clone().or_else(|| parent.color.clone()),
+    bold: own.bold.or(parent.bold),
+    italic: own.italic.or(parent.italic),
+    underlined: own.underlined.or(parent.underlined),
+    strikethrough: own.strikethrough.or(parent.strikethrough),
+    obfuscated: own.obfuscated.or(parent.obfuscated),
+    ..Default::default()
+  };
+  // the arguments of a translation take the style of the whole
+  let mut text = String::new();
+  component.push_content(lang, &mut text);
+  if !text.is_empty() {
+    match runs.last_mut() {
+      Some(last) if last.style == style => last.text.push_str(&text),
+      _ => runs.push(Run {
+        text,
+        style: style.clone(),
+      }),
+    }
+  }
+  for child in &component.extra {
+    push_runs(child, lang, &style, runs);
+  }
+}
+
+impl Component {
+  /// The text with bold, italic, underlined, struck through and obfuscated
+  /// parts marked up in Markdown, and everything else escaped. Text without
+  /// any of those styles comes back as it is, unescaped.
+  pub fn to_markdown(&self, lang: &Lang) -> String {
+    let runs = runs(self, lang);
+    if runs.iter().all(|run| marks(&run.style).is_empty()) {
+      return runs.into_iter().map(|run| run.text).collect();
+    }
+    let mut out = String::new();
+    let mut open: Vec<Mark> = Vec::new();
+    // where italic markers went, see below
+    let mut italics = Vec::new();
+    // whitespace is kept outside of markers, `** bold**` is no Markdown
+    let mut space = String::new();
+    for run in runs {
+      let core = run.text.trim();
+      if core.is_empty() {
+        space.push_str(&run.text);
+        continue;
+      }
+      let marks = marks(&run.style);
+      // markers have to close in reverse order, so everything opened after one
+      // which ends here is closed as well and opened again if still wanted
+      let keep = open
+        .iter()
+        .take_while(|mark| marks.contains(mark))
+        .count();
+      for mark in open.drain(keep..).rev() {
+        push_marker(&mut out, &mut italics, mark);
+      }
+      out.push_str(&space);
+      space.clear();
+      let start = run.text.len() - run.text.trim_start().len();
+      out.push_str(&run.text[..start]);
+      for mark in marks {
+        if !open.contains(&mark) {
+          push_marker(&mut out, &mut italics, mark);
+          open.push(mark);
+        }
+      }
+      escape_markdown(core, &mut out);
+      space.push_str(&run.text[start + core.len()..]);
+    }
+    for mark in open.into_iter().rev() {
+      push_marker(&mut out, &mut italics, mark);
+    }
+    out.push_str(&space);
+    // `_` inside a word is no emphasis, as in snake_case, but `*` is
+    for pos in italics {
+      if is_word(out[..pos].chars().next_back()) && is_word(out[pos + 1..].chars().next()) {
+        out.replace_range(pos..pos + 1, "*");
+      }
+    }
+    out
+  }
+
+  /// Parses the Markdown of chat platforms into a component with the same
+  /// styles. Markers without a partner, and code spans, stay as written.
+  pub fn from_markdown(markdown: &str) -> Component {
+    let mut tokens = tokenize(markdown);
+
+    // pairs each marker with the next one of the same kind, where anything
+    // opened between them and not yet closed is left unpaired
+    let mut paired = vec![false; tokens.len()];
+    let mut stack: Vec<(usize, Mark)> = Vec::new();
+    for i in 0..tokens.len() {
+      // `___` reads as `__` then `_`, but closing an italic inside an
+      // underline it is meant the other way around
+      let swap = match (&tokens[i], tokens.get(i + 1)) {
+        (Token::Mark(outer, raw), Some(Token::Mark(inner, next))) => {
+          raw[..1] == next[..1]
+            && stack.last().map(|(_, open)| open) == Some(inner)
+            && stack.iter().any(|(_, open)| open == outer)
+        }
+        _ => false,
+      };
+      if swap {
+        tokens.swap(i, i + 1);
+      }
+      if let Token::Mark(mark, _) = &tokens[i] {
+        match stack.iter().rposition(|(_, open)| open == mark) {
+          Some(pos) => {
+            paired[stack[pos].0] = true;
+            paired[i] = true;
+            stack.truncate(pos);
+          }
+          None => stack.push((i, *mark)),
+        }
+      }
+    }
+
+    let mut root = Component::default();
+    let mut active: Vec<Mark> = Vec::new();
+    let mut text = String::new();
+    for (token, paired) in tokens.into_iter().zip(paired) {
+      match token {
+        Token::Mark(mark, _) if paired => {
+          push_text(&mut root, &mut text, &active);
+          match active.iter().position(|open| *open == mark) {
+            Some(pos) => {
+              active.remove(pos);
+            }
+            None => active.push(mark),
+          }
+        }
+        Token::Mark(_, raw) | Token::Text(raw) => text.push_str(&raw),
+      }
+    }
+    push_text(&mut root, &mut text, &active);
+    root
+  }
+
+  /// The text with legacy formatting codes, introduced by `code`: `§` for
+  /// where the client renders them, `&` for chat plugins translating them.
+  pub fn to_legacy(&self, lang: &Lang, code: char) -> String {
+    let mut out = String::new();
+    let mut current = Style::default();
+    for run in runs(self, lang) {
+      if run.style != current {
+        // a color resets the formats, and so does the reset code
+        let color = run.style.color.as_deref().and_then(legacy_color);
+        match color {
+          Some(color) => {
+            out.push(code);
+            out.push(color);
+          }
+          None if current != Style::default() => {
+            out.push(code);
+            out.push('r');
+          }
+          None => {}
+        }
+        for (flag, format) in [
+          (run.style.obfuscated, 'k'),
+          (run.style.bold, 'l'),
+          (run.style.strikethrough, 'm'),
+          (run.style.underlined, 'n'),
+          (run.style.italic, 'o'),
+        ] {
+          if flag == Some(true) {
+            out.push(code);
+            out.push(format);
+          }
+        }
+        current = run.style;
+      }
+      escape_codes(&run.text, code, &mut out);
+    }
+    out
+  }
+}
+
+/// Whether `c` makes a legacy formatting code when it follows the code
+/// character.
+pub fn is_format_code(c: &char) -> bool {
+  matches!(c.to_ascii_lowercase(), '0'..='9' | 'a'..='f' | 'k'..='o' | 'r')
+}
+
+enum Token {
+  Text(String),
+  // with the marker as written, for when it turns out to be text
+  Mark(Mark, String),
+}
+
+fn tokenize(markdown: &str) -> Vec<Token> {
+  let chars: Vec<char> = markdown.chars().collect();
+  let mut tokens = Vec::new();
+  let mut text = String::new();
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    let next = chars.get(i + 1).copied();
+    let mark = match (c, next) {
+      ('\\', Some(next)) if next.is_ascii_punctuation() => {
+        text.push(next);
+        i += 2;
+        continue;
+      }
+      ('`', _) => {
+        // a code span is shown as written, without its backticks
+        match chars[i + 1..].iter().position(|c| *c == '`') {
+          Some(len) => {
+            text.extend(&chars[i + 1..i + 1 + len]);
+            i += len + 2;
+          }
+          None => {
+            text.push(c);
+            i += 1;
+          }
+        }
+        continue;
+      }
+      ('*', Some('*')) => Some((Mark::Bold, 2)),
+      ('_', Some('_')) => Some((Mark::Underlined, 2)),
+      ('~', Some('~')) => Some((Mark::Strikethrough, 2)),
+      ('|', Some('|')) => Some((Mark::Obfuscated, 2)),
+      ('*', _) => Some((Mark::Italic, 1)),
+      // snake_case is no emphasis
+      ('_', _) if !(is_word(i.checked_sub(1).map(|i| chars[i])) && is_word(next)) => {
+        Some((Mark::Italic, 1))
+      }
+      _ => None,
+    };
+    match mark {
+      Some((mark, len)) => {
+        if !text.is_empty() {
+          tokens.push(Token::Text(std::mem::take(&mut text)));
+        }
+        tokens.push(Token::Mark(mark, chars[i..i + len].iter().collect()));
+        i += len;
+      }
+      None => {
+        text.push(c);
+        i += 1;
+      }
+    }
+  }
+  if !text.is_empty() {
+    tokens.push(Token::Text(text));
+  }
+  tokens
+}
+
+fn is_word(c: Option<char>) -> bool {
+  c.map_or(false, char::is_alphanumeric)
+}
+
+fn push_text(root: &mut Component, text: &mut String, active: &[Mark]) {
+  if text.is_empty() {
+    return;
+  }
+  let mut style = Style::default();
+  for mark in active {
+    mark.set(&mut style);
+  }
+  root.extra.push(Component {
+    content: Content::Text(std::mem::take(text)),
+    style,
+    extra: Vec::new(),
+  });
+}
+
+fn push_marker(out: &mut String, italics: &mut Vec<usize>, mark: Mark) {
+  if mark == Mark::Italic {
+    italics.push(out.len());
+  }
+  out.push_str(mark.marker());
+}
+
+fn escape_markdown(text: &str, out: &mut String) {
+  for c in text.chars() {
+    if matches!(c, '\\' | '*' | '_' | '~' | '|' | '`') {
+      out.push('\\');
+    }
+    out.push(c);
+  }
+}
+
+// text which reads like a code is kept as written by a zero width space after
+// the code character
+fn escape_codes(text: &str, code: char, out: &mut String) {
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    out.push(c);
+    if c == code && chars.peek().map_or(false, is_format_code) {
+      out.push('\u{200B}');
+    }
+  }
+}
+
+// the named colors, hex colors of 1.16 have no code
+fn legacy_color(color: &str) -> Option<char> {
+  let code = match color {
+    "black" => '0',
+    "dark_blue" => '1',
+    "dark_green" => '2',
+    "dark_aqua" => '3',
+    "dark_red" => '4',
+    "dark_purple" => '5',
+    "gold" => '6',
+    "gray" => '7',
+    "dark_gray" => '8',
+    "blue" => '9',
+    "green" => 'a',
+    "aqua" => 'b',
+    "red" => 'c',
+    "light_purple" => 'd',
+    "yellow" => 'e',
+    "white" => 'f',
+    _ => return None,
+  };
+  Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Mark::*, *};
+
+  fn line(parts: &[(&str, &[Mark])]) -> Component {
+    let mut root = Component::default();
+    for (text, marks) in parts {
+      let mut part = Component::text(*text);
+      for mark in *marks {
+        mark.set(&mut part.style);
+      }
+      root.extra.push(part);
+    }
+    root
+  }
+
+  // every character but whitespace, which moves out of markers, with its marks
+  fn visible(component: &Component, lang: &Lang) -> Vec<(char, Vec<Mark>)> {
+    runs(component, lang)
+      .iter()
+      .flat_map(|run| {
+        let marks = marks(&run.style);
+        run
+          .text
+          .chars()
+          .filter(|c| !c.is_whitespace())
+          .map(move |c| (c, marks.clone()))
+      })
+      .collect()
+  }
+
+  #[test]
+  fn round_trips_through_markdown() {
+    let lang = Lang::load("en_us").unwrap();
+    let lines = [
+      // overlapping
+      line(&[("bold ", &[Bold]), ("both", &[Bold, Italic]), (" italic", &[Italic])]),
+      line(&[("a", &[Italic]), ("b", &[Italic, Strikethrough]), ("c", &[Strikethrough])]),
+      // nested, inside a word
+      line(&[("a", &[Bold]), ("b", &[Bold, Italic]), ("c", &[Bold])]),
+      line(&[("a", &[Underlined, Italic]), ("b", &[])]),
+      line(&[("all", &[Bold, Italic, Strikethrough]), (" snake_case *x*", &[])]),
+    ];
+    for line in lines {
+      let markdown = line.to_markdown(&lang);
+      let back = Component::from_markdown(&markdown);
+      assert_eq!(visible(&back, &lang), visible(&line, &lang), "{}", markdown);
+      assert_eq!(back.to_markdown(&lang), markdown);
+    }
+  }
+
+  #[test]
+  fn writes_markdown() {
+    let lang = Lang::load("en_us").unwrap();
+    let overlapping =
+      line(&[("bold ", &[Bold]), ("both", &[Bold, Italic]), (" italic", &[Italic])]);
+    assert_eq!(overlapping.to_markdown(&lang), "**bold _both_** _italic_");
+    let nested = line(&[("a", &[Bold]), ("b", &[Bold, Italic]), ("c", &[Bold])]);
+    assert_eq!(nested.to_markdown(&lang), "**a*b*c**");
+    let escaped = line(&[("x", &[Bold]), (" snake_case", &[])]);
+    assert_eq!(escaped.to_markdown(&lang), "**x** snake\\_case");
+    // nothing to mark up, nothing to escape
+    let plain = line(&[("snake_case *x*", &[])]);
+    assert_eq!(plain.to_markdown(&lang), "snake_case *x*");
+  }
+
+  #[test]
+  fn writes_legacy_codes() {
+    let lang = Lang::load("en_us").unwrap();
+    let mut red = Component::text("a");
+    red.style.color = Some("red".to_owned());
+    red.style.bold = Some(true);
+    red.extra.push(Component::text("b"));
+    red.extra[0].style.bold = Some(false);
+    let line = Component {
+      extra: vec![red, Component::text(" &c is red")],
+      ..Default::default()
+    };
+    assert_eq!(line.to_legacy(&lang, '&'), "&c&la&cb&r &\u{200B}c is red");
+    // only the code character in front of a code is escaped
+    assert_eq!(Component::text("&c & §c").to_legacy(&lang, '&'), "&\u{200B}c & §c");
+
+    let nested = Component::from_markdown("**bold _both_** ~~gone~~");
+    assert_eq!(nested.to_legacy(&lang, '&'), "&lbold &r&l&oboth&r &mgone");
+  }
+}
@@ -0,0 +1,172 @@
+use std::{
+  collections::{hash_map::DefaultHasher, VecDeque},
+  hash::{Hash, Hasher},
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use crate::{
+  chat::convert::is_format_code,
+  config::{ChatConfig, CONFIG},
+  data::Sender,
+};
+
+// more lines than the bot can send within any sensible window
+const MAX_REMEMBERED: usize = 256;
+
+/// Recognises chat caused by the bot itself, so it is not relayed back to
+/// where it came from, and chat of the ignored players.
+#[derive(Debug)]
+pub struct EchoGuard {
+  bot_uuid: Option<Uuid>,
+  bot_name: String,
+  window: Duration,
+  ignored_uuids: Vec<Uuid>,
+  ignored_names: Vec<String>,
+  // the formatting codes the bot sends besides §, see `mesagisto.color_code`
+  color_code: Option<char>,
+  sent: Mutex<VecDeque<(u64, Instant)>>,
+}
+
+impl EchoGuard {
+  pub fn new(bot_uuid: Option<Uuid>, bot_name: String) -> Self {
+    let color_code = CONFIG.mesagisto.color_code.chars().next();
+    Self::with_config(bot_uuid, bot_name, &CONFIG.chat, color_code)
+  }
+
+  fn with_config(
+    bot_uuid: Option<Uuid>,
+    bot_name: String,
+    config: &ChatConfig,
+    color_code: Option<char>,
+  ) -> Self {
+    let (ignored_uuids, ignored_names) = config
+      .ignore
+      .iter()
+      .map(|entry| Uuid::parse_str(entry).map_err(|_| entry.to_lowercase()))
+      .partition::<Vec<_>, _>(Result::is_ok);
+    Self {
+      bot_uuid,
+      bot_name,
+      window: Duration::from_secs(config.echo_window),
+      ignored_uuids: ignored_uuids.into_iter().filter_map(Result::ok).collect(),
+      ignored_names: ignored_names.into_iter().filter_map(Result::err).collect(),
+      color_code,
+      sent: Default::default(),
+    }
+  }
+
+  /// Remembers a line the bot says in game.
+  pub fn record(&self, content: &str) {
+    let now = Instant::now();
+    let mut sent = self.sent.lock().unwrap();
+    self.expire(&mut sent, now);
+    if sent.len() == MAX_REMEMBERED {
+      sent.pop_front();
+    }
+    sent.push_back((self.fingerprint(content), now));
+  }
+
+  /// Whether `content` said by `sender` must not be relayed.
+  pub fn is_echo(&self, sender: &Sender, content: &str) -> bool {
+    if let Some(uuid) = sender.uuid {
+      if Some(uuid) == self.bot_uuid || self.ignored_uuids.contains(&uuid) {
+        return true;
+      }
+    }
+    let name = sender.name.to_lowercase();
+    if name == self.bot_name.to_lowercase() || self.ignored_names.contains(&name) {
+      return true;
+    }
+    // plugins may show the bot under a nickname, but not change what it said
+    self.was_sent(content)
+  }
+
+  /// Whether the bot said `content` recently, for lines without a sender such
+  /// as the output of `tellraw`.
+  pub fn was_sent(&self, content: &str) -> bool {
+    let fingerprint = self.fingerprint(content);
+    let mut sent = self.sent.lock().unwrap();
+    self.expire(&mut sent, Instant::now());
+    sent.iter().any(|(sent, _)| *sent == fingerprint)
+  }
+
+  fn expire(&self, sent: &mut VecDeque<(u64, Instant)>, now: Instant) {
+    while let Some((_, at)) = sent.front() {
+      if now.duration_since(*at) <= self.window {
+        break;
+      }
+      sent.pop_front();
+    }
+  }
+
+  // a hash of the content with formatting codes, case and spacing ignored, which
+  // servers and plugins tend to change
+  fn fingerprint(&self, content: &str) -> u64 {
+    let mut normalized = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+      if c == '§' {
+        chars.next();
+      } else if Some(c) == self.color_code && chars.peek().map_or(false, is_format_code) {
+        // shown as formatting, not as written
+        chars.next();
+      } else {
+        normalized.extend(c.to_lowercase());
+      }
+    }
+    let mut hasher = DefaultHasher::new();
+    for word in normalized.split_whitespace() {
+      word.hash(&mut hasher);
+    }
+    hasher.finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn guard(color_code: Option<char>) -> EchoGuard {
+    let config = ChatConfig {
+      ignore: vec![arcstr::literal!("OtherBot")],
+      ..Default::default()
+    };
+    EchoGuard::with_config(None, String::from("MesagistoBot"), &config, color_code)
+  }
+
+  fn sender(name: &str) -> Sender {
+    Sender {
+      name: name.to_owned(),
+      uuid: None,
+      prefix: None,
+    }
+  }
+
+  #[test]
+  fn recognises_the_bot_and_ignored_players() {
+    let echo = guard(None);
+    assert!(echo.is_echo(&sender("mesagistobot"), "anything"));
+    assert!(echo.is_echo(&sender("OtherBot"), "anything"));
+    assert!(!echo.is_echo(&sender("Steve"), "anything"));
+  }
+
+  #[test]
+  fn recognises_lines_without_a_sender() {
+    let echo = guard(None);
+    echo.record("[Mesagisto] bob: hello there");
+    // as a plugin broadcasts it, or a server shows it in a format of its own
+    assert!(echo.was_sent("§7[Mesagisto]  bob: Hello there"));
+    assert!(echo.is_echo(&sender("[Server]"), "[Mesagisto] bob: hello there"));
+    assert!(!echo.was_sent("[Mesagisto] bob: something else"));
+  }
+
+  #[test]
+  fn ignores_the_color_codes_the_bot_sends() {
+    let echo = guard(Some('&'));
+    echo.record("[Mesagisto] bob: &lbold&r and Tom & Jerry");
+    assert!(echo.was_sent("[Mesagisto] bob: bold and Tom & Jerry"));
+  }
+}
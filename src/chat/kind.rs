@@ -0,0 +1,33 @@
+use crate::config::{Route, CONFIG};
+
+/// Where a chat line is shown in game, which tells what it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatKind {
+  /// Said by a player, or by a plugin in place of one.
+  Chat,
+  /// Server announcements and command feedback.
+  System,
+  /// The action bar above the hotbar.
+  GameInfo,
+}
+
+impl ChatKind {
+  /// From the position byte of the chat packets.
+  pub fn from_position(position: u8) -> Self {
+    match position {
+      0 => ChatKind::Chat,
+      1 => ChatKind::System,
+      _ => ChatKind::GameInfo,
+    }
+  }
+
+  /// How lines of this kind are relayed, from the `chat.routes` config.
+  pub fn route(self) -> Route {
+    let routes = &CONFIG.chat.routes;
+    match self {
+      ChatKind::Chat => routes.chat,
+      ChatKind::System => routes.system,
+      ChatKind::GameInfo => routes.game_info,
+    }
+  }
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, fs, io};
+
+use color_eyre::eyre::{self, bail, WrapErr};
+use once_cell::sync::OnceCell;
+
+// the keys chat, deaths and advancements are built from, as worded in vanilla,
+// and the lines of the bot itself under `mesagisto.`
+const BUNDLED: &[(&str, &str)] = &[
+  ("en_us", include_str!("../../assets/lang/en_us.json")),
+  ("zh_cn", include_str!("../../assets/lang/zh_cn.json")),
+];
+// complete language files taken from the client jar go here
+const OVERRIDE_DIR: &str = "config/lang";
+
+static CURRENT: OnceCell<Lang> = OnceCell::new();
+
+/// A table of translation keys, as the client uses to render `translate`
+/// components.
+#[derive(Debug, Default)]
+pub struct Lang {
+  entries: HashMap<String, String>,
+}
+
+impl Lang {
+  /// Loads and installs the table for `locale`, which [Lang::current] returns
+  /// from then on.
+  pub fn init(locale: &str) -> eyre::Result<()> {
+    let lang = Lang::load(locale)?;
+    if CURRENT.set(lang).is_err() {
+      bail!("the language table is already initialized");
+    }
+    Ok(())
+  }
+
+  /// The table set up by [Lang::init], or the bundled en_us one before that.
+  pub fn current() -> &'static Lang {
+    CURRENT.get_or_init(|| {
+      let mut lang = Lang::default();
+      lang.extend_json(BUNDLED[0].1).unwrap();
+      lang
+    })
+  }
+
+  /// The bundled table of `locale` with `config/lang/<locale>.json` (or the
+  /// `.lang` file of 1.12 and older) on top. Keys missing from both fall back
+  /// to en_us, like in the client.
+  pub fn load(locale: &str) -> eyre::Result<Lang> {
+    let mut lang = Lang::default();
+    lang.extend_json(BUNDLED[0].1)?;
+    let bundled = BUNDLED.iter().find(|(name, _)| *name == locale);
+    if let Some((_, json)) = bundled {
+      lang.extend_json(json)?;
+    }
+    let mut found = bundled.is_some();
+    for (extension, legacy) in [("json", false), ("lang", true)] {
+      let path = format!("{}/{}.{}", OVERRIDE_DIR, locale, extension);
+      let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+        Err(err) => return Err(err).wrap_err_with(|| format!("failed to read {}", path)),
+      };
+      if legacy {
+        lang.extend_legacy(&content);
+      } else {
+        lang
+          .extend_json(&content)
+          .wrap_err_with(|| format!("invalid language file {}", path))?;
+      }
+      found = true;
+      break;
+    }
+    if !found {
+      bail!(
+        "no language `{}`, bundled are {}, others can be put into {}",
+        locale,
+        BUNDLED.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", "),
+        OVERRIDE_DIR
+      );
+    }
+    Ok(lang)
+  }
+
+  fn extend_json(&mut self, json: &str) -> eyre::Result<()> {
+    let entries: HashMap<String, String> = serde_json::from_str(json)?;
+    self.entries.extend(entries);
+    Ok(())
+  }
+
+  // `key=value` lines, with `#` comments
+  fn extend_legacy(&mut self, content: &str) {
+    let entries = content
+      .lines()
+      .filter(|line| !line.starts_with('#'))
+      .filter_map(|line| line.split_once('='))
+      .map(|(key, value)| (key.to_owned(), value.to_owned()));
+    self.entries.extend(entries);
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.entries.get(key).map(String::as_str)
+  }
+
+  /// Renders `key` with `args` the way the client does. The bundled tables
+  /// only hold what chat is made of, so a key missing from them shows its
+  /// arguments, the names and items the line is about, rather than the key.
+  /// Only a key without arguments is shown as it is, like the client does.
+  pub fn translate(&self, key: &str, args: &[String]) -> String {
+    if let Some(template) = self.get(key) {
+      return format(template, args);
+    }
+    let args: Vec<&str> = args
+      .iter()
+      .map(String::as_str)
+      .filter(|arg| !arg.is_empty())
+      .collect();
+    if args.is_empty() {
+      key.to_owned()
+    } else {
+      args.join(" ")
+    }
+  }
+}
+
+/// Fills a Java format string with `args`. Supports `%s`, the positional
+/// `%1$s` and the `%%` escape, which is all the language files use. Missing
+/// arguments render as nothing.
+pub fn format(template: &str, args: &[String]) -> String {
+  let mut out = String::with_capacity(template.len());
+  let mut next = 0;
+  let mut rest = template;
+  while let Some(start) = rest.find('%') {
+    out.push_str(&rest[..start]);
+    rest = &rest[start + 1..];
+    if let Some(after) = rest.strip_prefix('%') {
+      out.push('%');
+      rest = after;
+      continue;
+    }
+    if let Some(after) = rest.strip_prefix('s').or_else(|| rest.strip_prefix('d')) {
+      out.push_str(args.get(next).map(String::as_str).unwrap_or_default());
+      next += 1;
+      rest = after;
+      continue;
+    }
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let positional = rest[digits..]
+      .strip_prefix("$s")
+      .or_else(|| rest[digits..].strip_prefix("$d"));
+    match (rest[..digits].parse::<usize>(), positional) {
+      (Ok(index), Some(after)) if index > 0 => {
+        out.push_str(args.get(index - 1).map(String::as_str).unwrap_or_default());
+        rest = after;
+      }
+      // not a placeholder, keep it verbatim
+      _ => out.push('%'),
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn formats_placeholders() {
+    assert_eq!(format("%s joined the game", &args(&["Steve"])), "Steve joined the game");
+    assert_eq!(format("%2$s %1$s", &args(&["a", "b"])), "b a");
+    assert_eq!(format("100%% %s", &args(&[])), "100% ");
+  }
+
+  #[test]
+  fn shows_the_arguments_of_unknown_keys() {
+    let lang = Lang::load("en_us").unwrap();
+    let joined = lang.translate("multiplayer.player.joined", &args(&["Steve"]));
+    assert_eq!(joined, "Steve joined the game");
+    let unknown = lang.translate("death.attack.unknown_to_us", &args(&["Steve", "", "Zombie"]));
+    assert_eq!(unknown, "Steve Zombie");
+    assert_eq!(lang.translate("gui.unknown_to_us", &[]), "gui.unknown_to_us");
+  }
+}
@@ -0,0 +1,10 @@
+pub mod component;
+pub mod convert;
+pub mod echo;
+pub mod kind;
+pub mod lang;
+pub mod pattern;
+pub mod sanitize;
+pub mod tellraw;
+
+pub use self::{component::Component, lang::Lang};
@@ -0,0 +1,161 @@
+use arcstr::ArcStr;
+use color_eyre::eyre::{self, bail, ensure, WrapErr};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::config::CONFIG;
+
+const PRESET_PREFIX: &str = "preset:";
+const PRESETS: &[(&str, &str)] = &[
+  // `<Steve> hello`, also used by plugins which keep the vanilla look
+  ("vanilla", r"^<(?P<sender>[^<>\s]+)> (?s:(?P<message>.+))$"),
+];
+
+/// Who said what, taken from a chat line by one of the [Patterns].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parsed {
+  pub sender: String,
+  pub prefix: Option<String>,
+  pub message: String,
+}
+
+/// The configured chat patterns, tried in order on the flattened text of chat
+/// lines that are not in a vanilla chat format.
+#[derive(Debug)]
+pub struct Patterns {
+  entries: Vec<(ArcStr, Regex)>,
+}
+
+impl Patterns {
+  /// Compiles `entries`, which are regexes with named `sender` and `message`
+  /// groups and an optional `prefix` group, or `preset:<name>`.
+  pub fn compile(entries: &[ArcStr]) -> eyre::Result<Self> {
+    let entries = entries
+      .iter()
+      .map(|entry| {
+        let regex = compile(entry).wrap_err_with(|| format!("invalid pattern `{}`", entry))?;
+        Ok((entry.clone(), regex))
+      })
+      .collect::<eyre::Result<_>>()?;
+    Ok(Self { entries })
+  }
+
+  /// The patterns from the `chat` section of the config.
+  pub fn current() -> &'static Patterns {
+    static CURRENT: Lazy<Patterns> =
+      Lazy::new(|| Patterns::compile(&CONFIG.chat.patterns).expect("patterns are validated"));
+    &CURRENT
+  }
+
+  pub fn parse(&self, plain: &str) -> Option<Parsed> {
+    self.explain(plain).map(|(_, parsed)| parsed)
+  }
+
+  /// Like [Patterns::parse], also telling which pattern matched.
+  pub fn explain(&self, plain: &str) -> Option<(&str, Parsed)> {
+    self.entries.iter().find_map(|(entry, regex)| {
+      let captures = regex.captures(plain)?;
+      let group = |name| {
+        captures
+          .name(name)
+          .map(|m| m.as_str().trim())
+          .filter(|s| !s.is_empty())
+      };
+      let parsed = Parsed {
+        sender: group("sender")?.to_owned(),
+        prefix: group("prefix").map(str::to_owned),
+        message: group("message")?.to_owned(),
+      };
+      Some((entry.as_str(), parsed))
+    })
+  }
+}
+
+fn compile(entry: &str) -> eyre::Result<Regex> {
+  let source = match entry.strip_prefix(PRESET_PREFIX) {
+    Some(name) => match PRESETS.iter().find(|(preset, _)| *preset == name) {
+      Some((_, source)) => *source,
+      None => bail!(
+        "unknown preset, available are {}",
+        PRESETS
+          .iter()
+          .map(|(name, _)| *name)
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+    },
+    None => entry,
+  };
+  let regex = Regex::new(source)?;
+  let names: Vec<_> = regex.capture_names().flatten().collect();
+  for required in ["sender", "message"] {
+    ensure!(
+      names.contains(&required),
+      "the `{}` group is missing",
+      required
+    );
+  }
+  Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn patterns(entries: &[&str]) -> Patterns {
+    let entries: Vec<ArcStr> = entries.iter().map(|entry| ArcStr::from(*entry)).collect();
+    Patterns::compile(&entries).unwrap()
+  }
+
+  #[test]
+  fn parses_plugin_formats() {
+    let patterns = patterns(&[
+      r"^\[(?P<prefix>[^\]]+)\] (?P<sender>\w+): (?P<message>.+)$",
+      "preset:vanilla",
+    ]);
+    assert_eq!(
+      patterns.parse("[Admin] Steve: hello: there"),
+      Some(Parsed {
+        sender: "Steve".into(),
+        prefix: Some("Admin".into()),
+        message: "hello: there".into(),
+      })
+    );
+    assert_eq!(
+      patterns.parse("<Alex> hi"),
+      Some(Parsed {
+        sender: "Alex".into(),
+        prefix: None,
+        message: "hi".into(),
+      })
+    );
+    assert_eq!(patterns.parse("Steve joined the game"), None);
+  }
+
+  #[test]
+  fn first_match_wins() {
+    let first = r"^(?P<sender>\w+): (?P<message>.+)$";
+    let second = r"^(?P<prefix>\w+): (?P<sender>\w+) (?P<message>.+)$";
+    let (entry, parsed) = patterns(&[first, second])
+      .explain("Steve: hello there")
+      .map(|(entry, parsed)| (entry.to_owned(), parsed))
+      .unwrap();
+    assert_eq!(entry, first);
+    assert_eq!(parsed.sender, "Steve");
+    assert_eq!(parsed.message, "hello there");
+
+    let (entry, parsed) = patterns(&[second, first])
+      .explain("Steve: hello there")
+      .map(|(entry, parsed)| (entry.to_owned(), parsed))
+      .unwrap();
+    assert_eq!(entry, second);
+    assert_eq!(parsed.sender, "hello");
+  }
+
+  #[test]
+  fn rejects_missing_groups_and_presets() {
+    for entry in [r"^(?P<sender>\w+): (?P<text>.+)$", "preset:essentials"] {
+      assert!(Patterns::compile(&[ArcStr::from(entry)]).is_err(), "{}", entry);
+    }
+  }
+}
@@ -0,0 +1,159 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+// 1.11 raised the limit from 100 characters
+const LONG_CHAT_SINCE: i32 = 315;
+
+/// How long a chat message the server accepts before kicking the sender. The
+/// server counts UTF-16 units, as Java strings do.
+pub fn chat_limit(protocol_version: i32) -> usize {
+  if protocol_version >= LONG_CHAT_SINCE {
+    256
+  } else {
+    100
+  }
+}
+
+/// Turns text into something the server accepts from a client: formatting
+/// codes and control characters are removed, and whitespace, newlines
+/// included, collapses into single spaces.
+pub fn sanitize(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut chars = text.chars();
+  let mut space = false;
+  while let Some(c) = chars.next() {
+    if c == '§' {
+      // the code after it goes as well
+      chars.next();
+    } else if c.is_whitespace() {
+      space = !out.is_empty();
+    } else if !c.is_control() {
+      if space {
+        out.push(' ');
+        space = false;
+      }
+      out.push(c);
+    }
+  }
+  out
+}
+
+/// Splits sanitized text into chat lines of at most `limit` UTF-16 units,
+/// between words where possible and never inside a grapheme. At most
+/// `max_lines` come back, the last one marked when text was cut off.
+pub fn split(text: &str, limit: usize, max_lines: usize) -> Vec<String> {
+  let mut lines = Lines {
+    lines: Vec::new(),
+    line: String::new(),
+    len: 0,
+    limit,
+  };
+  for word in text.split_word_bounds() {
+    let len = utf16_len(word);
+    if lines.len + len <= lines.limit {
+      lines.push(word, len);
+    } else if len <= lines.limit {
+      lines.break_line();
+      lines.push(word, len);
+    } else {
+      // a word longer than a whole line, such as a link
+      for grapheme in word.graphemes(true) {
+        let len = utf16_len(grapheme);
+        if lines.len + len > lines.limit {
+          lines.break_line();
+        }
+        if len <= lines.limit {
+          lines.push(grapheme, len);
+        }
+      }
+    }
+  }
+  lines.break_line();
+
+  let mut lines = lines.lines;
+  if lines.len() > max_lines {
+    lines.truncate(max_lines);
+    if let Some(last) = lines.last_mut() {
+      mark_cut(last, limit);
+    }
+  }
+  lines
+}
+
+struct Lines {
+  lines: Vec<String>,
+  line: String,
+  // in UTF-16 units
+  len: usize,
+  limit: usize,
+}
+
+impl Lines {
+  fn push(&mut self, text: &str, len: usize) {
+    // spaces at the start of a continuation line would be trimmed anyway
+    if self.line.is_empty() && text.trim().is_empty() {
+      return;
+    }
+    // the server runs a line starting with a slash as a command of the bot, a
+    // zero width space in front keeps it chat and is neither trimmed nor
+    // disallowed. It counts towards the limit like any other unit.
+    if self.line.is_empty() && text.starts_with('/') {
+      self.line.push(ZWSP);
+      self.len += ZWSP.len_utf16();
+    }
+    self.line.push_str(text);
+    self.len += len;
+  }
+
+  fn break_line(&mut self) {
+    let line = self.line.trim_end();
+    if !line.is_empty() {
+      self.lines.push(line.to_owned());
+    }
+    self.line.clear();
+    self.len = 0;
+  }
+}
+
+const ZWSP: char = '\u{200B}';
+
+fn mark_cut(line: &mut String, limit: usize) {
+  const MARK: &str = "…";
+  let mut len = utf16_len(line);
+  let mut graphemes: Vec<&str> = line.graphemes(true).collect();
+  while len + utf16_len(MARK) > limit {
+    match graphemes.pop() {
+      Some(grapheme) => len -= utf16_len(grapheme),
+      None => break,
+    }
+  }
+  *line = format!("{}{}", graphemes.concat().trim_end(), MARK);
+}
+
+/// The length of `text` as Java counts it.
+pub fn utf16_len(text: &str) -> usize {
+  text.chars().map(char::len_utf16).sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn splits_between_words() {
+    assert_eq!(split("aaa bbb ccc", 7, 5), ["aaa bbb", "ccc"]);
+    assert_eq!(split("aaaaaaaaaa", 4, 5), ["aaaa", "aaaa", "aa"]);
+    assert_eq!(split("aaa bbb ccc ddd", 7, 1), ["aaa bb…"]);
+  }
+
+  #[test]
+  fn escapes_commands_within_the_limit() {
+    let line = format!("/{}", "a".repeat(9));
+    let lines = split(&line, 10, 5);
+    assert!(lines.iter().all(|line| utf16_len(line) <= 10), "{:?}", lines);
+    assert!(lines[0].starts_with("\u{200B}/"));
+    assert_eq!(lines.concat().replace(ZWSP, ""), line);
+    // continuation lines starting with a slash are escaped as well
+    let lines = split("aaaa /bb", 5, 5);
+    assert_eq!(lines, ["aaaa", "\u{200B}/bb"]);
+  }
+}
@@ -0,0 +1,293 @@
+use std::{
+  collections::VecDeque,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+  },
+  time::{Duration, Instant},
+};
+
+use chrono::Local;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::{
+  component::{ClickEvent, Content, Style},
+  sanitize, Component, Lang,
+};
+use crate::{config::CONFIG, data::RemoteMessage};
+
+// how long after a command an error is taken as the answer to it
+const ANSWER_WINDOW: Duration = Duration::from_secs(10);
+
+// the op level `/tellraw` needs, which the server announces to the bot
+const TELLRAW_OP_LEVEL: u8 = 2;
+
+// what servers answer to a command the bot may not use, since 1.13 commands
+// without permission are unknown. Only a second line of defence behind the op
+// level, plain text answers such as those of Spigot could as well be a player
+// saying the same and are not taken.
+const REFUSAL_KEYS: &[&str] = &[
+  "commands.generic.permission",
+  "commands.generic.notFound",
+  "command.unknown.command",
+];
+// the line after an unknown command, pointing at it
+const CONTEXT_KEY: &str = "command.context.here";
+
+static URL: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://[^\s<>]+").unwrap());
+
+/// A remote message as a `tellraw` command, together with the chat lines to
+/// send instead when the server refuses it.
+#[derive(Debug)]
+pub struct Rich {
+  pub command: String,
+  // what players see, to recognise the message when it comes back
+  pub text: String,
+  pub fallback: Vec<String>,
+}
+
+/// Whether remote messages are sent with `tellraw` on a connection: when the
+/// op level of the bot allows it, and until the server refuses the command.
+#[derive(Debug)]
+pub struct Tellraw {
+  enabled: AtomicBool,
+  // None until the server announces it, old servers never do
+  op_level: Mutex<Option<u8>>,
+  // fallbacks of the commands sent recently, taken when they are refused
+  sent: Mutex<VecDeque<(Instant, Vec<String>)>>,
+}
+
+impl Tellraw {
+  pub fn new(enabled: bool) -> Self {
+    Self {
+      enabled: AtomicBool::new(enabled),
+      op_level: Mutex::new(None),
+      sent: Default::default(),
+    }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Relaxed)
+      && self
+        .op_level
+        .lock()
+        .unwrap()
+        .map_or(true, |level| level >= TELLRAW_OP_LEVEL)
+  }
+
+  /// Records the op level the server gave the bot. Returns whether that just
+  /// turned `tellraw` off.
+  pub fn set_op_level(&self, level: u8) -> bool {
+    let was_enabled = self.is_enabled();
+    *self.op_level.lock().unwrap() = Some(level);
+    was_enabled && !self.is_enabled()
+  }
+
+  /// Remembers a command which was just sent.
+  pub fn sent(&self, fallback: Vec<String>) {
+    let mut sent = self.sent.lock().unwrap();
+    expire(&mut sent);
+    sent.push_back((Instant::now(), fallback));
+  }
+
+  /// Whether a system message is the server refusing a recent command. A
+  /// refusal turns `tellraw` off and returns the lines to send instead of the
+  /// oldest command not yet refused, as the server answers in order. Refusals of which there is nothing
+  /// left to send return no lines but must not be relayed either.
+  pub fn refused(&self, chat: &Component) -> Option<Vec<String>> {
+    let key = match &chat.content {
+      Content::Translate { key, .. } => key.as_str(),
+      _ => return None,
+    };
+    let mut sent = self.sent.lock().unwrap();
+    expire(&mut sent);
+    if sent.is_empty() {
+      return None;
+    }
+    if key == CONTEXT_KEY {
+      return Some(Vec::new());
+    }
+    if !REFUSAL_KEYS.contains(&key) {
+      return None;
+    }
+    self.enabled.store(false, Ordering::Relaxed);
+    let fallback = sent
+      .iter_mut()
+      .map(|(_, fallback)| std::mem::take(fallback))
+      .find(|fallback| !fallback.is_empty());
+    Some(fallback.unwrap_or_default())
+  }
+}
+
+/// The op level an entity status announces to a player about itself.
+pub fn op_level(entity_status: i8) -> Option<u8> {
+  match entity_status {
+    24..=28 => Some((entity_status - 24) as u8),
+    _ => None,
+  }
+}
+
+fn expire(sent: &mut VecDeque<(Instant, Vec<String>)>) {
+  while let Some((at, _)) = sent.front() {
+    if at.elapsed() <= ANSWER_WINDOW {
+      break;
+    }
+    sent.pop_front();
+  }
+}
+
+/// Builds the `tellraw` command showing `message`, and the text players see.
+/// Hover details are left out when the command would be too long for chat,
+/// and None comes back when it is too long even without them.
+pub fn build(message: &RemoteMessage, limit: usize, lang: &Lang) -> Option<(String, String)> {
+  let config = &CONFIG.tellraw;
+  let content = sanitize::sanitize(&message.content);
+  let content = if CONFIG.mesagisto.markdown {
+    Component::from_markdown(&content)
+  } else {
+    Component::text(content)
+  };
+  let tag = message.platform.as_deref().unwrap_or(&message.channel);
+  let hover = Component::text(format!(
+    "Platform: {}\nChannel: {}\nTime: {}",
+    message.platform.as_deref().unwrap_or("unknown"),
+    message.channel,
+    message.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+  ));
+  let mut tag = Component {
+    style: Style {
+      color: Some(config.tag_color.to_string()),
+      hover: Some(Box::new(hover)),
+      ..Default::default()
+    },
+    ..Component::text(format!("[{}] ", sanitize::sanitize(tag)))
+  };
+  let sender = Component::text(format!("{}: ", sanitize::sanitize(&message.sender)));
+
+  let text = format!("{}{}{}", tag.to_plain(lang), sender.to_plain(lang), content.to_plain(lang));
+  let mut line = Component {
+    extra: vec![tag.clone(), sender, linkify(content)],
+    ..Default::default()
+  };
+  let mut command = to_command(&line);
+  if sanitize::utf16_len(&command) > limit {
+    tag.style.hover = None;
+    line.extra[0] = tag;
+    command = to_command(&line);
+  }
+  if sanitize::utf16_len(&command) > limit {
+    return None;
+  }
+  Some((command, text))
+}
+
+fn to_command(line: &Component) -> String {
+  // a § in chat gets the bot kicked, JSON allows escaping it
+  let json = line.to_json().to_string().replace('§', "\\u00a7");
+  format!("/tellraw @a {}", json)
+}
+
+// turns the URLs in the text into links
+fn linkify(mut component: Component) -> Component {
+  component.extra = component.extra.into_iter().map(linkify).collect();
+  let text = match &component.content {
+    Content::Text(text) if URL.is_match(text) => text.clone(),
+    _ => return component,
+  };
+  let mut pieces = Vec::new();
+  let mut last = 0;
+  for url in URL.find_iter(&text) {
+    if url.start() > last {
+      pieces.push(Component::text(&text[last..url.start()]));
+    }
+    pieces.push(Component {
+      style: Style {
+        color: Some(String::from("aqua")),
+        underlined: Some(true),
+        click: Some(ClickEvent {
+          action: String::from("open_url"),
+          value: url.as_str().to_owned(),
+        }),
+        ..Default::default()
+      },
+      ..Component::text(url.as_str())
+    });
+    last = url.end();
+  }
+  if last < text.len() {
+    pieces.push(Component::text(&text[last..]));
+  }
+  pieces.append(&mut component.extra);
+  Component {
+    content: Content::default(),
+    style: component.style,
+    extra: pieces,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn translate(key: &str) -> Component {
+    Component {
+      content: Content::Translate {
+        key: key.to_owned(),
+        with: Vec::new(),
+      },
+      ..Default::default()
+    }
+  }
+
+  fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+  }
+
+  #[test]
+  fn resends_refused_commands_in_order() {
+    let tellraw = Tellraw::new(true);
+    assert_eq!(tellraw.refused(&translate("command.unknown.command")), None);
+    tellraw.sent(lines(&["first", "line"]));
+    tellraw.sent(lines(&["second"]));
+    let refused = tellraw.refused(&translate("command.unknown.command"));
+    assert_eq!(refused, Some(lines(&["first", "line"])));
+    assert!(!tellraw.is_enabled());
+    // the line pointing at the command belongs to the refusal before it
+    assert_eq!(tellraw.refused(&translate("command.context.here")), Some(Vec::new()));
+    let refused = tellraw.refused(&translate("commands.generic.permission"));
+    assert_eq!(refused, Some(lines(&["second"])));
+    let refused = tellraw.refused(&translate("commands.generic.permission"));
+    assert_eq!(refused, Some(Vec::new()));
+  }
+
+  #[test]
+  fn decides_from_the_op_level() {
+    assert_eq!(op_level(24), Some(0));
+    assert_eq!(op_level(28), Some(4));
+    assert_eq!(op_level(22), None);
+    let tellraw = Tellraw::new(true);
+    // assumed allowed until the server tells
+    assert!(tellraw.is_enabled());
+    assert!(tellraw.set_op_level(0));
+    assert!(!tellraw.is_enabled());
+    assert!(!tellraw.set_op_level(0));
+    // opped later
+    assert!(!tellraw.set_op_level(2));
+    assert!(tellraw.is_enabled());
+    // never on when turned off in the config
+    let off = Tellraw::new(false);
+    assert!(!off.set_op_level(4));
+    assert!(!off.is_enabled());
+  }
+
+  #[test]
+  fn takes_only_translated_refusals() {
+    let tellraw = Tellraw::new(true);
+    tellraw.sent(lines(&["line"]));
+    let said = Component::text("Unknown command. Type \"/help\" for help.");
+    assert_eq!(tellraw.refused(&said), None);
+    assert_eq!(tellraw.refused(&translate("multiplayer.player.joined")), None);
+    assert!(tellraw.is_enabled());
+  }
+}
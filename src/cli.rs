@@ -0,0 +1,46 @@
+use color_eyre::eyre::{self, bail};
+
+use crate::{
+  chat::{pattern::Patterns, Component, Lang},
+  config::CONFIG,
+};
+
+/// `--parse-chat <line>...`: shows how each line would be split into sender
+/// and message with the current config, without connecting anywhere. A line
+/// may also be a JSON chat component, as servers send them.
+pub fn parse_chat(lines: &[String]) -> eyre::Result<()> {
+  if lines.is_empty() {
+    bail!("usage: --parse-chat <line>...");
+  }
+  Lang::init(&CONFIG.chat.lang)?;
+  let patterns = Patterns::compile(&CONFIG.chat.patterns)?;
+  for line in lines {
+    println!("line:     {}", line);
+    let component = match serde_json::from_str(line) {
+      Ok(json) => Component::from_json(&json),
+      Err(_) => Component::text(line.as_str()),
+    };
+    let chat_line = component.to_chat_line(Lang::current());
+    if let Some(sender) = chat_line.sender {
+      println!("format:   {}", chat_line.key.unwrap_or_default());
+      println!("sender:   {}", sender);
+      println!("message:  {}", chat_line.message);
+      if let Some(styled) = chat_line.styled {
+        println!("markdown: {}", styled.to_markdown(Lang::current()));
+      }
+    } else {
+      println!("text:     {}", chat_line.message);
+      match patterns.explain(&chat_line.message) {
+        Some((pattern, parsed)) => {
+          println!("pattern:  {}", pattern);
+          println!("prefix:   {}", parsed.prefix.unwrap_or_default());
+          println!("sender:   {}", parsed.sender);
+          println!("message:  {}", parsed.message);
+        }
+        None => println!("no pattern matched, the line is not relayed as player chat"),
+      }
+    }
+    println!();
+  }
+  Ok(())
+}
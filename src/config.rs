@@ -0,0 +1,576 @@
+use std::net::ToSocketAddrs;
+
+use arcstr::ArcStr;
+use color_eyre::eyre::{self, ensure, eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::chat::pattern::Patterns;
+
+#[basic_derive]
+#[derive(AutomaticConfig)]
+#[location = "config/mc.yml"]
+pub struct Config {
+  #[educe(Default = false)]
+  pub enable: bool,
+  // A-z order
+  // from before bindings, only read to tell where it went
+  #[serde(skip_serializing_if = "Option::is_none")]
+  account: Option<serde_yaml::Value>,
+  #[educe(Default(expression = "vec![BindingConfig::default()]"))]
+  pub bindings: Vec<BindingConfig>,
+  pub chat: ChatConfig,
+  pub client: ClientConfig,
+  pub mesagisto: MesagistoConfig,
+  pub query: QueryConfig,
+  pub queue: QueueConfig,
+  pub reconnect: ReconnectConfig,
+  // from before bindings, only read to tell where it went
+  #[serde(skip_serializing_if = "Option::is_none")]
+  server: Option<serde_yaml::Value>,
+  pub tablist: TablistConfig,
+  pub tellraw: TellrawConfig,
+  pub throttle: ThrottleConfig,
+}
+
+impl Config {
+  pub fn validate(&self) -> eyre::Result<()> {
+    // kept in the file until moved by hand, dropping them would lose the account
+    ensure!(
+      self.account.is_none() && self.server.is_none() && self.mesagisto.channel.is_none(),
+      "the `server` and `account` sections and `mesagisto.channel` have moved into `bindings`, \
+       a list of servers each with a `name`, `server`, `account` and `channel`: move them into \
+       a binding and remove them from config/mc.yml"
+    );
+    ensure!(!self.bindings.is_empty(), "at least one binding is required");
+    for (i, binding) in self.bindings.iter().enumerate() {
+      binding
+        .validate()
+        .wrap_err_with(|| format!("invalid binding `{}`", binding.name))?;
+      ensure!(
+        self.bindings[..i].iter().all(|other| other.name != binding.name),
+        "binding name `{}` is used more than once",
+        binding.name
+      );
+    }
+    self.chat.validate().wrap_err("invalid `chat` section")?;
+    self.client.validate().wrap_err("invalid `client` section")?;
+    self
+      .mesagisto
+      .validate()
+      .wrap_err("invalid `mesagisto` section")?;
+    self.queue.validate().wrap_err("invalid `queue` section")?;
+    self
+      .reconnect
+      .validate()
+      .wrap_err("invalid `reconnect` section")?;
+    self.tellraw.validate().wrap_err("invalid `tellraw` section")?;
+    self
+      .throttle
+      .validate()
+      .wrap_err("invalid `throttle` section")?;
+    Ok(())
+  }
+}
+
+/// One Minecraft server bridged to one Mesagisto channel, with the account
+/// the bot joins as.
+#[basic_derive]
+pub struct BindingConfig {
+  // shown in the logs
+  #[educe(Default = "default")]
+  pub name: ArcStr,
+  pub server: ServerConfig,
+  pub account: AccountConfig,
+  #[educe(Default = "")]
+  pub channel: ArcStr,
+}
+
+impl BindingConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(!self.name.is_empty(), "name must not be empty");
+    self.server.validate().wrap_err("invalid `server` section")?;
+    self.account.validate().wrap_err("invalid `account` section")?;
+    ensure!(
+      !self.channel.is_empty(),
+      "channel must be set to the Mesagisto channel to bridge"
+    );
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct ServerConfig {
+  // pattern: "{host}:{port}"
+  #[educe(Default = "127.0.0.1:25565")]
+  pub address: ArcStr,
+  // used when the server does not answer the status ping, 340 is 1.12.2
+  #[educe(Default = 340)]
+  pub protocol_version: i32,
+}
+
+impl ServerConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    let (host, port) = self
+      .address
+      .rsplit_once(':')
+      .ok_or_else(|| eyre!("address `{}` must be in the form host:port", self.address))?;
+    ensure!(!host.is_empty(), "address `{}` has an empty host", self.address);
+    port
+      .parse::<u16>()
+      .map_err(|_| eyre!("address `{}` has an invalid port `{}`", self.address, port))?;
+    ensure!(
+      self.protocol_version > 0,
+      "protocol_version must be positive, got {}",
+      self.protocol_version
+    );
+    // only a sanity check, the address may not be resolvable at startup
+    if let Err(err) = self.address.to_socket_addrs() {
+      tracing::warn!(
+        target: crate::TARGET,
+        "Unable to resolve server address {}: {}",
+        self.address,
+        err
+      );
+    }
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct AccountConfig {
+  pub mode: AccountMode,
+  // the in-game name for offline accounts, the login email otherwise
+  #[educe(Default = "MesagistoBot")]
+  pub username: ArcStr,
+  #[educe(Default = "")]
+  pub password: ArcStr,
+  pub yggdrasil: YggdrasilConfig,
+  pub authlib_injector: AuthlibInjectorConfig,
+  pub microsoft: MicrosoftConfig,
+}
+
+impl AccountConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    match self.mode {
+      AccountMode::Offline => validate_username(&self.username)?,
+      AccountMode::Microsoft => {
+        ensure!(
+          !self.microsoft.client_id.is_empty(),
+          "microsoft.client_id is required, register an Azure application with public client \
+           flows enabled to get one"
+        );
+      }
+      AccountMode::Yggdrasil | AccountMode::AuthlibInjector => {
+        ensure!(
+          !self.username.is_empty() && !self.password.is_empty(),
+          "username and password are required for {:?} accounts",
+          self.mode
+        );
+      }
+    }
+    if self.mode == AccountMode::AuthlibInjector {
+      ensure!(
+        !self.authlib_injector.auth_server.is_empty()
+          && !self.authlib_injector.session_server.is_empty(),
+        "authlib_injector.auth_server and authlib_injector.session_server are required, \
+         usually {{api_root}}/authserver/ and {{api_root}}/sessionserver/"
+      );
+    }
+    Ok(())
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountMode {
+  #[default]
+  Offline,
+  Yggdrasil,
+  AuthlibInjector,
+  Microsoft,
+}
+
+#[basic_derive]
+pub struct YggdrasilConfig {
+  #[educe(Default = "https://authserver.mojang.com/")]
+  pub auth_server: ArcStr,
+  #[educe(Default = "https://sessionserver.mojang.com/")]
+  pub session_server: ArcStr,
+}
+
+#[basic_derive]
+pub struct AuthlibInjectorConfig {
+  // pattern: "https://{skin site}/api/yggdrasil/authserver/"
+  #[educe(Default = "")]
+  pub auth_server: ArcStr,
+  // pattern: "https://{skin site}/api/yggdrasil/sessionserver/"
+  #[educe(Default = "")]
+  pub session_server: ArcStr,
+}
+
+#[basic_derive]
+pub struct MicrosoftConfig {
+  #[educe(Default = "")]
+  pub client_id: ArcStr,
+  #[educe(Default = "https://login.microsoftonline.com/")]
+  pub oauth_server: ArcStr,
+  #[educe(Default = "https://user.auth.xboxlive.com/")]
+  pub xbox_server: ArcStr,
+  #[educe(Default = "https://xsts.auth.xboxlive.com/")]
+  pub xsts_server: ArcStr,
+  #[educe(Default = "https://api.minecraftservices.com/")]
+  pub minecraft_server: ArcStr,
+  #[educe(Default = "https://sessionserver.mojang.com/")]
+  pub session_server: ArcStr,
+}
+
+pub(crate) fn validate_username(username: &str) -> eyre::Result<()> {
+  ensure!(
+    (3..=16).contains(&username.len()),
+    "username `{}` must be 3 to 16 characters long",
+    username
+  );
+  ensure!(
+    username
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+    "username `{}` may only contain letters, digits and underscores",
+    username
+  );
+  Ok(())
+}
+
+#[basic_derive]
+pub struct ClientConfig {
+  #[educe(Default = "en_GB")]
+  pub locale: ArcStr,
+  #[educe(Default = 2)]
+  pub view_distance: u8,
+  // 0: enabled, 1: commands only, 2: hidden
+  #[educe(Default = 0)]
+  pub chat_mode: i32,
+  #[educe(Default = false)]
+  pub chat_colors: bool,
+  #[educe(Default = 0)]
+  pub displayed_skin_parts: u8,
+  // 0: left, 1: right
+  #[educe(Default = 0)]
+  pub main_hand: i32,
+}
+
+impl ClientConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(
+      (2..=32).contains(&self.view_distance),
+      "view_distance must be between 2 and 32, got {}",
+      self.view_distance
+    );
+    ensure!(
+      (0..=2).contains(&self.chat_mode),
+      "chat_mode must be 0, 1 or 2, got {}",
+      self.chat_mode
+    );
+    ensure!(
+      (0..=1).contains(&self.main_hand),
+      "main_hand must be 0 or 1, got {}",
+      self.main_hand
+    );
+    ensure!(!self.locale.is_empty(), "locale must not be empty");
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct ChatConfig {
+  // language chat is rendered in, en_us and zh_cn are bundled, others are read
+  // from config/lang/{lang}.json
+  #[educe(Default = "en_us")]
+  pub lang: ArcStr,
+  // seconds for which a line the bot said is recognised when it comes back
+  #[educe(Default = 10)]
+  pub echo_window: u64,
+  // names or uuids of other bots and NPCs whose chat is not relayed
+  pub ignore: Vec<ArcStr>,
+  // tried in order on chat which is not in a vanilla format, each a regex with
+  // named groups `sender`, `message` and optionally `prefix`, or a preset like
+  // preset:vanilla, try them with --parse-chat '<line>'
+  // example: '^\[(?P<prefix>[^\]]+)\] (?P<sender>\w+) » (?P<message>.+)$'
+  #[educe(Default(expression = "vec![arcstr::literal!(\"preset:vanilla\")]"))]
+  pub patterns: Vec<ArcStr>,
+  pub routes: RoutesConfig,
+}
+
+#[basic_derive]
+pub struct RoutesConfig {
+  // lines in the chat box
+  #[educe(Default(expression = "Route::Relay"))]
+  pub chat: Route,
+  // announcements and command feedback
+  #[educe(Default(expression = "Route::Notice"))]
+  pub system: Route,
+  // the action bar, mostly minigame plugins
+  #[educe(Default(expression = "Route::Drop"))]
+  pub game_info: Route,
+}
+
+/// What happens to a kind of chat line.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Route {
+  /// Relayed as said by its sender, or as a notice when none is found.
+  Relay,
+  /// Relayed as a whole, as a notice from the server.
+  Notice,
+  Drop,
+}
+
+impl ChatConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(
+      !self.lang.is_empty()
+        && self
+          .lang
+          .chars()
+          .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+      "lang `{}` must be a locale like en_us",
+      self.lang
+    );
+    ensure!(self.echo_window > 0, "echo_window must be at least 1 second");
+    ensure!(
+      self.ignore.iter().all(|entry| !entry.is_empty()),
+      "ignore must not contain empty entries"
+    );
+    Patterns::compile(&self.patterns)?;
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct MesagistoConfig {
+  // pattern: "nats://{host}:{port}"
+  #[educe(Default = "nats://nats.mesagisto.org:4222")]
+  pub nats: ArcStr,
+  #[educe(Default = "this is an example of cipher key")]
+  pub cipher_key: ArcStr,
+  // from before bindings, only read to tell where it went
+  #[serde(skip_serializing_if = "Option::is_none")]
+  channel: Option<ArcStr>,
+  // how remote messages are shown in game, with {sender} and {message}
+  #[educe(Default = "[Mesagisto] {sender}: {message}")]
+  pub format: ArcStr,
+  // lines a long remote message is split into at most, the rest is cut off
+  #[educe(Default = 4)]
+  pub max_lines: usize,
+  // relay bold, italic, underlined and struck through chat as Markdown, and
+  // show the Markdown of remote messages without its markers
+  #[educe(Default = true)]
+  pub markdown: bool,
+  // for servers letting the bot use color codes in chat, such as "&" with
+  // EssentialsX, remote Markdown is then sent with these codes
+  #[educe(Default = "")]
+  pub color_code: ArcStr,
+}
+
+impl MesagistoConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(
+      self.nats.starts_with("nats://") || self.nats.starts_with("tls://"),
+      "nats address `{}` must start with nats:// or tls://",
+      self.nats
+    );
+    ensure!(!self.cipher_key.is_empty(), "cipher_key must not be empty");
+    ensure!(
+      self.format.contains("{message}"),
+      "format `{}` must contain {{message}}",
+      self.format
+    );
+    ensure!(self.max_lines > 0, "max_lines must be at least 1");
+    ensure!(
+      self.color_code.chars().count() <= 1 && !self.color_code.contains('§'),
+      "color_code `{}` must be a single character other than §, or empty",
+      self.color_code
+    );
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct ReconnectConfig {
+  #[educe(Default = true)]
+  pub enable: bool,
+  // seconds
+  #[educe(Default = 5)]
+  pub initial_delay: u64,
+  // seconds
+  #[educe(Default = 300)]
+  pub max_delay: u64,
+  // 0 means retrying forever
+  #[educe(Default = 0)]
+  pub max_attempts: u32,
+}
+
+impl ReconnectConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(self.initial_delay > 0, "initial_delay must be at least 1 second");
+    ensure!(
+      self.max_delay >= self.initial_delay,
+      "max_delay ({}) must not be smaller than initial_delay ({})",
+      self.max_delay,
+      self.initial_delay
+    );
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct QueryConfig {
+  // a remote message asking who is online, answered from the tab list without
+  // going into the game, empty to turn it off
+  #[educe(Default = "/mc list")]
+  pub list_command: ArcStr,
+  // seconds before the same channel is answered again
+  #[educe(Default = 10)]
+  pub cooldown: u64,
+  // list the game mode and latency of each player as well
+  #[educe(Default = false)]
+  pub details: bool,
+}
+
+#[basic_derive]
+pub struct QueueConfig {
+  // packets read from the server and not yet handled
+  #[educe(Default = 1024)]
+  pub read_capacity: usize,
+  // packets waiting to be written to the server
+  #[educe(Default = 256)]
+  pub write_capacity: usize,
+}
+
+impl QueueConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(self.read_capacity > 0, "read_capacity must be positive");
+    ensure!(self.write_capacity > 0, "write_capacity must be positive");
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct TablistConfig {
+  // announce players joining and leaving as seen in the tab list, in place of
+  // the join and leave messages of the server
+  #[educe(Default = true)]
+  pub announce: bool,
+  // seconds after connecting in which the server sends everyone already
+  // online, who are not announced
+  #[educe(Default = 5)]
+  pub quiet_period: u64,
+}
+
+#[basic_derive]
+pub struct TellrawConfig {
+  // show remote messages with /tellraw, with the platform in color and details
+  // on hover, if the bot may use the command, in plain chat otherwise
+  #[educe(Default = false)]
+  pub enable: bool,
+  // a color name like aqua, or #rrggbb since 1.16
+  #[educe(Default = "aqua")]
+  pub tag_color: ArcStr,
+}
+
+impl TellrawConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    let hex = self.tag_color.len() == 7
+      && self.tag_color.starts_with('#')
+      && self.tag_color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    let named = !self.tag_color.is_empty()
+      && self
+        .tag_color
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c == '_');
+    ensure!(
+      hex || named,
+      "tag_color `{}` must be a color name like aqua or #rrggbb",
+      self.tag_color
+    );
+    Ok(())
+  }
+}
+
+#[basic_derive]
+pub struct ThrottleConfig {
+  // chat lines and commands sent per second in the long run, vanilla kicks
+  // above about one per second
+  #[educe(Default = 1.0)]
+  pub rate: f64,
+  // lines sent at once after a quiet period
+  #[educe(Default = 5)]
+  pub burst: u32,
+  // lines waiting to be sent, unbounded with the delay overflow
+  #[educe(Default = 20)]
+  pub pending: usize,
+  // what happens to a line when the pending lines are full
+  #[educe(Default(expression = "Overflow::Merge"))]
+  pub overflow: Overflow,
+  // sent in place of the lines merged away, with {count}
+  #[educe(Default = "[Mesagisto] {count} more messages were not shown")]
+  pub summary: ArcStr,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overflow {
+  /// The oldest pending line makes room for the new one.
+  DropOldest,
+  /// New lines are dropped and counted in one summary line.
+  Merge,
+  /// Every line is kept until it is sent, however many pile up.
+  Delay,
+}
+
+impl ThrottleConfig {
+  fn validate(&self) -> eyre::Result<()> {
+    ensure!(
+      self.rate.is_finite() && self.rate > 0.0,
+      "rate must be positive, got {}",
+      self.rate
+    );
+    ensure!(self.burst > 0, "burst must be at least 1");
+    ensure!(self.pending > 0, "pending must be at least 1");
+    ensure!(
+      self.summary.contains("{count}"),
+      "summary `{}` must contain {{count}}",
+      self.summary
+    );
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rejects_sections_from_before_bindings() {
+    let config: Config = serde_yaml::from_str("server:\n  address: example.com:25565\n").unwrap();
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("moved into `bindings`"), "{}", err);
+    // saving keeps them until they are moved
+    assert!(serde_yaml::to_string(&config).unwrap().contains("example.com"));
+
+    let config: Config = serde_yaml::from_str("mesagisto:\n  channel: survival\n").unwrap();
+    assert!(config.validate().is_err());
+  }
+
+  #[test]
+  fn rejects_chat_patterns_without_a_group() {
+    let chat: ChatConfig =
+      serde_yaml::from_str("patterns:\n  - '^(?P<sender>\\w+): (?P<msg>.+)$'\n").unwrap();
+    let err = chat.validate().unwrap_err();
+    assert!(
+      err.chain().any(|cause| cause.to_string() == "the `message` group is missing"),
+      "{:?}",
+      err
+    );
+    assert!(ChatConfig::default().validate().is_ok());
+  }
+}
@@ -1,4 +1,42 @@
-struct ChatMessage {
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// A player as seen in chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sender {
+  pub name: String,
+  // only known when the server sends it along with the message
+  pub uuid: Option<Uuid>,
+  // a rank or title shown before the name by chat plugins
+  pub prefix: Option<String>,
+}
+
+/// A chat line said in game, on its way to Mesagisto.
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+  pub sender: Sender,
+  pub content: String,
+  pub timestamp: DateTime<Utc>,
+}
+
+impl ChatMessage {
+  pub fn new(sender: Sender, content: String) -> Self {
+    Self {
+      sender,
+      content,
+      timestamp: Utc::now(),
+    }
+  }
+}
+
+/// A message from another platform of the channel, on its way into the game.
+#[derive(Debug, Clone)]
+pub struct RemoteMessage {
   pub sender: String,
   pub content: String,
+  // only known when the sending client tells it
+  pub platform: Option<String>,
+  pub channel: String,
+  // when it was received, Mesagisto messages carry no time of their own
+  pub timestamp: DateTime<Utc>,
 }
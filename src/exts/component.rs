@@ -0,0 +1,26 @@
+use steven_protocol::format::Component;
+
+pub trait ComponentExt {
+  /// The text of the component and all of its `extra` children, without any
+  /// formatting.
+  fn to_plain(&self) -> String;
+}
+
+impl ComponentExt for Component {
+  fn to_plain(&self) -> String {
+    let mut out = String::new();
+    push_plain(self, &mut out);
+    out
+  }
+}
+
+fn push_plain(component: &Component, out: &mut String) {
+  match component {
+    Component::Text(text) => {
+      out.push_str(&text.text);
+      for child in text.modifier.extra.iter().flatten() {
+        push_plain(child, out);
+      }
+    }
+  }
+}
@@ -0,0 +1,16 @@
+use steven_protocol::protocol::{Serializable, UUID};
+use uuid::Uuid;
+
+pub trait UuidExt {
+  /// The uuid, or `None` for the nil uuid servers send for nobody.
+  fn to_uuid(&self) -> Option<Uuid>;
+}
+
+impl UuidExt for UUID {
+  fn to_uuid(&self) -> Option<Uuid> {
+    // serialized as two big-endian longs, the layout `Uuid` uses
+    let mut buf = Vec::with_capacity(16);
+    self.write_to(&mut buf).ok()?;
+    Uuid::from_slice(&buf).ok().filter(|uuid| !uuid.is_nil())
+  }
+}
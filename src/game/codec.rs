@@ -0,0 +1,453 @@
+use std::io::{self, Read, Write};
+
+use aes::Aes128;
+use bytes::{Buf, BytesMut};
+use cfb8::{
+  cipher::{AsyncStreamCipher, NewCipher},
+  Cfb8,
+};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use futures::{SinkExt, StreamExt};
+use steven_protocol::protocol::{
+  self, forge,
+  packet::{self, Packet},
+  Direction, Serializable, State, VarInt,
+};
+use tokio::net::{
+  tcp::{OwnedReadHalf, OwnedWriteHalf},
+  TcpStream,
+};
+use tokio_util::codec::{Decoder, Encoder, Framed, FramedRead, FramedWrite};
+use tracing::trace;
+
+use super::{
+  outbound::{Outbound, WriteError},
+  player_info,
+  signed_chat::{self, Decoded},
+  TARGET,
+};
+use crate::chat::Component;
+
+type Aes128Cfb8 = Cfb8<Aes128>;
+
+pub type PacketReader = FramedRead<OwnedReadHalf, PacketCodec>;
+pub type PacketWriter = FramedWrite<OwnedWriteHalf, PacketCodec>;
+
+// packets are at most 2097151 bytes long, so the length prefix fits in 3 bytes
+const MAX_PACKET_LEN: usize = (1 << 21) - 1;
+
+/// A decoded packet. `steven_protocol` only keeps the text of chat components,
+/// so chat and disconnect packets come with the full [Component] as well.
+#[derive(Debug)]
+pub struct Inbound {
+  pub packet: Packet,
+  pub chat: Option<Component>,
+}
+
+/// Frames `steven_protocol` packets over a byte stream. Decodes clientbound
+/// packets and encodes serverbound ones, with the VarInt length prefix, zlib
+/// compression and AES/CFB8 encryption of the vanilla protocol.
+pub struct PacketCodec {
+  protocol_version: i32,
+  state: State,
+  // negative when compression is disabled
+  compression_threshold: i32,
+  encryptor: Option<Aes128Cfb8>,
+  decryptor: Option<Aes128Cfb8>,
+  // how many bytes at the start of the read buffer are already decrypted
+  decrypted: usize,
+}
+
+impl PacketCodec {
+  pub fn new(protocol_version: i32) -> Self {
+    Self {
+      protocol_version,
+      state: State::Handshaking,
+      compression_threshold: -1,
+      encryptor: None,
+      decryptor: None,
+      decrypted: 0,
+    }
+  }
+
+  pub fn set_state(&mut self, state: State) {
+    self.state = state;
+  }
+
+  pub fn set_compression(&mut self, threshold: i32) {
+    self.compression_threshold = threshold;
+  }
+
+  pub fn enable_encryption(&mut self, shared_secret: &[u8]) {
+    // the shared secret is both the key and the iv
+    self.encryptor = Some(Aes128Cfb8::new_from_slices(shared_secret, shared_secret).unwrap());
+    self.decryptor = Some(Aes128Cfb8::new_from_slices(shared_secret, shared_secret).unwrap());
+  }
+
+  /// Splits into a decoding and an encoding half, each keeping only its own
+  /// cipher, since the CFB8 stream state differs per direction.
+  fn split(self) -> (PacketCodec, PacketCodec) {
+    let read = PacketCodec {
+      encryptor: None,
+      decryptor: self.decryptor,
+      ..self
+    };
+    let write = PacketCodec {
+      encryptor: self.encryptor,
+      decryptor: None,
+      decrypted: 0,
+      ..read
+    };
+    (read, write)
+  }
+
+  fn unpack(&self, frame: BytesMut) -> Result<(i32, io::Cursor<Vec<u8>>), protocol::Error> {
+    let mut cursor = io::Cursor::new(frame.to_vec());
+    if self.compression_threshold >= 0 {
+      let data_len = VarInt::read_from(&mut cursor)?.0;
+      if data_len != 0 {
+        let compressed = &cursor.get_ref()[cursor.position() as usize..];
+        let mut data = Vec::with_capacity(data_len as usize);
+        ZlibDecoder::new(compressed).read_to_end(&mut data)?;
+        cursor = io::Cursor::new(data);
+      }
+    }
+    let id = VarInt::read_from(&mut cursor)?.0;
+    Ok((id, cursor))
+  }
+}
+
+// returns the value and the number of bytes it took, or None if incomplete
+fn peek_varint(src: &[u8]) -> Result<Option<(usize, usize)>, protocol::Error> {
+  let mut value = 0usize;
+  for (i, byte) in src.iter().enumerate().take(3) {
+    value |= ((byte & 0x7F) as usize) << (7 * i);
+    if byte & 0x80 == 0 {
+      return Ok(Some((value, i + 1)));
+    }
+  }
+  if src.len() >= 3 {
+    return Err(protocol::Error::Err(format!(
+      "packet length exceeds {} bytes",
+      MAX_PACKET_LEN
+    )));
+  }
+  Ok(None)
+}
+
+impl Decoder for PacketCodec {
+  type Error = protocol::Error;
+  type Item = Inbound;
+
+  fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Inbound>, protocol::Error> {
+    if let Some(cipher) = &mut self.decryptor {
+      cipher.decrypt(&mut src[self.decrypted..]);
+    }
+    self.decrypted = src.len();
+
+    loop {
+      let (len, header) = match peek_varint(src)? {
+        Some(v) => v,
+        None => return Ok(None),
+      };
+      if src.len() < header + len {
+        src.reserve(header + len - src.len());
+        return Ok(None);
+      }
+      src.advance(header);
+      let frame = src.split_to(len);
+      self.decrypted -= header + len;
+
+      let (id, mut cursor) = self.unpack(frame)?;
+      if self.state == State::Play {
+        let decoded = match signed_chat::decode(self.protocol_version, id, &mut cursor)? {
+          Decoded::Other => player_info::decode(self.protocol_version, id, &mut cursor)?,
+          decoded => decoded,
+        };
+        match decoded {
+          Decoded::Packet(inbound) => return Ok(Some(inbound)),
+          Decoded::Skip => continue,
+          Decoded::Other => {}
+        }
+      }
+      let body = cursor.position() as usize;
+      match packet::packet_by_id(
+        self.protocol_version,
+        self.state,
+        Direction::Clientbound,
+        id,
+        &mut cursor,
+      )? {
+        Some(packet) => {
+          let chat = match packet {
+            Packet::ServerMessage_Sender(_)
+            | Packet::ServerMessage_Position(_)
+            | Packet::ServerMessage_NoPosition(_)
+            | Packet::ActionBar(_)
+            | Packet::Disconnect(_)
+            | Packet::LoginDisconnect(_) => read_chat(&cursor.get_ref()[body..]),
+            _ => None,
+          };
+          return Ok(Some(Inbound { packet, chat }));
+        }
+        None => trace!(
+          target: TARGET,
+          "Skipping unknown packet 0x{:X} in {:?}",
+          id,
+          self.state
+        ),
+      }
+    }
+  }
+}
+
+// the component is the first field of every chat, action bar and disconnect
+// packet
+fn read_chat(body: &[u8]) -> Option<Component> {
+  let json = String::read_from(&mut io::Cursor::new(body)).ok()?;
+  match serde_json::from_str(&json) {
+    Ok(value) => Some(Component::from_json(&value)),
+    Err(err) => {
+      trace!(target: TARGET, "Chat component is not valid JSON: {} {}", err, json);
+      None
+    }
+  }
+}
+
+impl Encoder<Outbound> for PacketCodec {
+  type Error = WriteError;
+
+  fn encode(&mut self, packet: Outbound, dst: &mut BytesMut) -> Result<(), WriteError> {
+    packet.check(self.state)?;
+    let mut body = Vec::new();
+    VarInt(packet.packet_id(self.protocol_version)).write_to(&mut body)?;
+    packet.write_to(self.protocol_version, &mut body)?;
+
+    let frame = if self.compression_threshold < 0 {
+      body
+    } else if body.len() >= self.compression_threshold as usize {
+      let mut frame = Vec::new();
+      VarInt(body.len() as i32).write_to(&mut frame)?;
+      let mut encoder = ZlibEncoder::new(frame, Compression::default());
+      encoder.write_all(&body)?;
+      encoder.finish()?
+    } else {
+      let mut frame = Vec::with_capacity(body.len() + 1);
+      VarInt(0).write_to(&mut frame)?;
+      frame.extend_from_slice(&body);
+      frame
+    };
+
+    let mut header = Vec::with_capacity(3);
+    VarInt(frame.len() as i32).write_to(&mut header)?;
+    let start = dst.len();
+    dst.extend_from_slice(&header);
+    dst.extend_from_slice(&frame);
+    if let Some(cipher) = &mut self.encryptor {
+      cipher.encrypt(&mut dst[start..]);
+    }
+    Ok(())
+  }
+}
+
+const DEFAULT_PORT: u16 = 25565;
+
+// "host:port", "[ipv6]:port", or a host alone on the default port
+fn split_address(address: &str) -> Result<(&str, u16), protocol::Error> {
+  let invalid = || protocol::Error::Err(format!("invalid address {}", address));
+  let parse_port = |port: &str| port.parse().map_err(|_| invalid());
+  if let Some(rest) = address.strip_prefix('[') {
+    let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+    return match rest.strip_prefix(':') {
+      Some(port) => Ok((host, parse_port(port)?)),
+      None if rest.is_empty() => Ok((host, DEFAULT_PORT)),
+      None => Err(invalid()),
+    };
+  }
+  match address.rsplit_once(':') {
+    // more than one colon is an IPv6 address without brackets, and no port
+    Some((host, _)) if host.contains(':') => Ok((address, DEFAULT_PORT)),
+    Some((host, port)) => Ok((host, parse_port(port)?)),
+    None => Ok((address, DEFAULT_PORT)),
+  }
+}
+
+/// A connection still in the handshake or login state, before it is split
+/// into a reader and a writer for the play state.
+pub struct Connection {
+  framed: Framed<TcpStream, PacketCodec>,
+  pub host: String,
+  pub port: u16,
+}
+
+impl Connection {
+  pub async fn connect(address: &str, protocol_version: i32) -> Result<Self, protocol::Error> {
+    let (host, port) = split_address(address)?;
+    let stream = TcpStream::connect((host, port)).await?;
+    stream.set_nodelay(true)?;
+    Ok(Self {
+      framed: Framed::new(stream, PacketCodec::new(protocol_version)),
+      host: host.to_owned(),
+      port,
+    })
+  }
+
+  pub async fn read_inbound(&mut self) -> Result<Inbound, protocol::Error> {
+    match self.framed.next().await {
+      Some(inbound) => inbound,
+      None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+    }
+  }
+
+  pub async fn write_packet(&mut self, packet: impl Into<Outbound>) -> Result<(), protocol::Error> {
+    Ok(self.framed.send(packet.into()).await?)
+  }
+
+  pub async fn write_fml2_handshake_plugin_message(
+    &mut self,
+    message_id: i32,
+    message: &forge::fml2::FmlHandshake,
+  ) -> Result<(), protocol::Error> {
+    let mut inner = Vec::new();
+    message.write_to(&mut inner)?;
+    let mut data = Vec::new();
+    "fml:handshake".to_owned().write_to(&mut data)?;
+    VarInt(inner.len() as i32).write_to(&mut data)?;
+    data.extend(inner);
+    self
+      .write_packet(packet::login::serverbound::LoginPluginResponse {
+        message_id: VarInt(message_id),
+        successful: true,
+        data,
+      })
+      .await
+  }
+
+  pub fn set_state(&mut self, state: State) {
+    self.framed.codec_mut().set_state(state);
+  }
+
+  pub fn set_compression(&mut self, threshold: i32) {
+    self.framed.codec_mut().set_compression(threshold);
+  }
+
+  pub fn compression_threshold(&self) -> i32 {
+    self.framed.codec().compression_threshold
+  }
+
+  pub fn enable_encryption(&mut self, shared_secret: &[u8]) {
+    self.framed.codec_mut().enable_encryption(shared_secret);
+  }
+
+  pub fn into_split(self) -> (PacketReader, PacketWriter) {
+    let parts = self.framed.into_parts();
+    let (read_half, write_half) = parts.io.into_split();
+    let (read_codec, write_codec) = parts.codec.split();
+    let mut reader = FramedRead::new(read_half, read_codec);
+    // keep whatever the server sent right after the login success
+    *reader.read_buffer_mut() = parts.read_buf;
+    let writer = FramedWrite::new(write_half, write_codec);
+    (reader, writer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::exts::component::ComponentExt;
+
+  const SECRET: [u8; 16] = [7; 16];
+
+  // a reading and a writing codec in the login state, as both ends would be
+  fn codecs(threshold: i32, encrypted: bool) -> (PacketCodec, PacketCodec) {
+    let mut codec = PacketCodec::new(340);
+    codec.set_state(State::Login);
+    codec.set_compression(threshold);
+    if encrypted {
+      codec.enable_encryption(&SECRET);
+    }
+    codec.split()
+  }
+
+  // the serverbound LoginStart has the layout of the clientbound Disconnect
+  // with the same id, a single string, so it decodes as one
+  fn disconnect(text: &str) -> Outbound {
+    packet::login::serverbound::LoginStart {
+      username: json!({ "text": text }).to_string(),
+    }
+    .into()
+  }
+
+  fn reason(inbound: Option<Inbound>) -> String {
+    match inbound.map(|inbound| inbound.packet) {
+      Some(Packet::LoginDisconnect(v)) => v.reason.to_plain(),
+      _ => panic!("not a disconnect"),
+    }
+  }
+
+  fn text(len: usize) -> String {
+    (0..len).map(|i| char::from(b'a' + (i * 7 % 26) as u8)).collect()
+  }
+
+  #[test]
+  fn roundtrips_packets() {
+    let texts = [String::from("hi"), text(1000), String::from("bye")];
+    for (threshold, encrypted) in [(-1, false), (256, false), (-1, true), (256, true)] {
+      let (mut read, mut write) = codecs(threshold, encrypted);
+      let mut bytes = BytesMut::new();
+      for text in &texts {
+        write.encode(disconnect(text), &mut bytes).unwrap();
+      }
+      for text in &texts {
+        assert_eq!(&reason(read.decode(&mut bytes).unwrap()), text);
+      }
+      assert!(read.decode(&mut bytes).unwrap().is_none());
+      assert!(bytes.is_empty());
+    }
+  }
+
+  #[test]
+  fn compresses_above_the_threshold() {
+    let (_, mut write) = codecs(256, false);
+    let mut small = BytesMut::new();
+    write.encode(disconnect("hi"), &mut small).unwrap();
+    // the length, then a data length of 0 for an uncompressed body
+    assert_eq!(small[1], 0);
+    let mut large = BytesMut::new();
+    write.encode(disconnect(&"a".repeat(1000)), &mut large).unwrap();
+    assert!(large.len() < 1000);
+  }
+
+  #[test]
+  fn decodes_frames_split_across_reads() {
+    // uncompressed, the length takes two bytes and is split between them too
+    for (threshold, len) in [(-1, 300), (256, 1000)] {
+      let text = text(len);
+      let (_, mut write) = codecs(threshold, true);
+      let mut bytes = BytesMut::new();
+      write.encode(disconnect(&text), &mut bytes).unwrap();
+      for at in 1..bytes.len() {
+        let (mut read, _) = codecs(threshold, true);
+        let mut src = BytesMut::from(&bytes[..at]);
+        assert!(read.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&bytes[at..]);
+        assert_eq!(reason(read.decode(&mut src).unwrap()), text);
+      }
+    }
+  }
+
+  #[test]
+  fn splits_addresses() {
+    assert_eq!(split_address("example.com:25566").unwrap(), ("example.com", 25566));
+    assert_eq!(split_address("example.com").unwrap(), ("example.com", 25565));
+    assert_eq!(split_address("127.0.0.1:25565").unwrap(), ("127.0.0.1", 25565));
+    assert_eq!(split_address("[::1]:25566").unwrap(), ("::1", 25566));
+    assert_eq!(split_address("[::1]").unwrap(), ("::1", 25565));
+    assert_eq!(split_address("::1").unwrap(), ("::1", 25565));
+    assert!(split_address("[::1").is_err());
+    assert!(split_address("[::1]x").is_err());
+    assert!(split_address("example.com:port").is_err());
+  }
+}
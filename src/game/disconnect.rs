@@ -0,0 +1,228 @@
+use std::fmt;
+
+use steven_protocol::format;
+
+use crate::{
+  chat::{component::Content, Component, Lang},
+  exts::component::ComponentExt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectKind {
+  Banned,
+  NotWhitelisted,
+  ServerFull,
+  ServerClosed,
+  DuplicateLogin,
+  TimedOut,
+  /// 1.19+ servers which only accept players with signed chat.
+  SecureChatRequired,
+  Other,
+}
+
+impl DisconnectKind {
+  // vanilla translation keys, a key also covers the ones below it such as
+  // `multiplayer.disconnect.banned.reason`
+  const KEYS: &'static [(&'static str, DisconnectKind)] = &[
+    ("multiplayer.disconnect.banned", DisconnectKind::Banned),
+    ("multiplayer.disconnect.banned_ip", DisconnectKind::Banned),
+    ("multiplayer.disconnect.ip_banned", DisconnectKind::Banned),
+    ("multiplayer.disconnect.not_whitelisted", DisconnectKind::NotWhitelisted),
+    ("multiplayer.disconnect.server_full", DisconnectKind::ServerFull),
+    ("multiplayer.disconnect.server_shutdown", DisconnectKind::ServerClosed),
+    ("multiplayer.disconnect.duplicate_login", DisconnectKind::DuplicateLogin),
+    ("multiplayer.disconnect.idling", DisconnectKind::TimedOut),
+    ("disconnect.timeout", DisconnectKind::TimedOut),
+    ("multiplayer.disconnect.missing_public_key", DisconnectKind::SecureChatRequired),
+    ("multiplayer.disconnect.invalid_public_key", DisconnectKind::SecureChatRequired),
+    ("multiplayer.disconnect.expired_public_key", DisconnectKind::SecureChatRequired),
+    ("multiplayer.disconnect.unsigned_chat", DisconnectKind::SecureChatRequired),
+    ("multiplayer.disconnect.chat_validation_failed", DisconnectKind::SecureChatRequired),
+  ];
+
+  // the vanilla english texts, for servers and proxies that send plain text
+  const TEXTS: &'static [(&'static str, DisconnectKind)] = &[
+    ("you are banned", DisconnectKind::Banned),
+    ("banned from this server", DisconnectKind::Banned),
+    ("not white-listed", DisconnectKind::NotWhitelisted),
+    ("not whitelisted", DisconnectKind::NotWhitelisted),
+    ("server is full", DisconnectKind::ServerFull),
+    ("server closed", DisconnectKind::ServerClosed),
+    ("server is restarting", DisconnectKind::ServerClosed),
+    ("logged in from another location", DisconnectKind::DuplicateLogin),
+    ("timed out", DisconnectKind::TimedOut),
+    ("requires secure profiles", DisconnectKind::SecureChatRequired),
+    ("missing or invalid signature", DisconnectKind::SecureChatRequired),
+  ];
+
+  /// Classifies on the translation keys of the reason, and only falls back to
+  /// its text when none of them is known.
+  pub fn classify(reason: &Component) -> Self {
+    Self::from_keys(reason)
+      .unwrap_or_else(|| Self::classify_text(&reason.to_plain(Lang::current())))
+  }
+
+  pub fn classify_text(reason: &str) -> Self {
+    let reason = reason.to_lowercase();
+    Self::TEXTS
+      .iter()
+      .find(|(pattern, _)| reason.contains(pattern))
+      .map(|(_, kind)| *kind)
+      .unwrap_or(DisconnectKind::Other)
+  }
+
+  // plugins often wrap the vanilla reason in an empty text with it as extra
+  fn from_keys(reason: &Component) -> Option<Self> {
+    if let Content::Translate { key, .. } = &reason.content {
+      let known = Self::KEYS.iter().find(|(known, _)| {
+        key
+          .strip_prefix(known)
+          .map_or(false, |rest| rest.is_empty() || rest.starts_with('.'))
+      });
+      if let Some((_, kind)) = known {
+        return Some(*kind);
+      }
+    }
+    reason.extra.iter().find_map(Self::from_keys)
+  }
+
+  /// Whether reconnecting has a chance to succeed. Retrying after a duplicate
+  /// login would only kick whoever is using the account now.
+  pub fn should_retry(self) -> bool {
+    !matches!(
+      self,
+      DisconnectKind::Banned
+        | DisconnectKind::NotWhitelisted
+        | DisconnectKind::DuplicateLogin
+        | DisconnectKind::SecureChatRequired
+    )
+  }
+
+  /// What the operator can do about it, when there is something.
+  pub fn hint(self) -> Option<&'static str> {
+    match self {
+      DisconnectKind::SecureChatRequired => Some(
+        "the server enforces secure chat, which the bot does not sign, set \
+         `enforce-secure-profile=false` in its server.properties",
+      ),
+      _ => None,
+    }
+  }
+}
+
+/// The server closed the connection on purpose, either while logging in or
+/// during play.
+#[derive(Debug, Clone)]
+pub struct Kicked {
+  pub kind: DisconnectKind,
+  pub reason: String,
+}
+
+impl Kicked {
+  /// `reason` is parsed from the raw JSON of the packet, `fallback` is the
+  /// text `steven_protocol` kept, used when the JSON could not be parsed.
+  pub fn new(reason: Option<&Component>, fallback: &format::Component) -> Self {
+    match reason {
+      Some(reason) => Self {
+        kind: DisconnectKind::classify(reason),
+        reason: reason.to_plain(Lang::current()),
+      },
+      None => {
+        let reason = fallback.to_plain();
+        Self {
+          kind: DisconnectKind::classify_text(&reason),
+          reason,
+        }
+      }
+    }
+  }
+}
+
+impl fmt::Display for Kicked {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "bot was kicked: {}", self.reason)
+  }
+}
+
+impl std::error::Error for Kicked {}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn classify(value: serde_json::Value) -> DisconnectKind {
+    DisconnectKind::classify(&Component::from_json(&value))
+  }
+
+  #[test]
+  fn classifies_translation_keys() {
+    let cases = [
+      ("multiplayer.disconnect.banned", DisconnectKind::Banned),
+      ("multiplayer.disconnect.not_whitelisted", DisconnectKind::NotWhitelisted),
+      ("multiplayer.disconnect.server_full", DisconnectKind::ServerFull),
+      ("multiplayer.disconnect.server_shutdown", DisconnectKind::ServerClosed),
+      ("multiplayer.disconnect.duplicate_login", DisconnectKind::DuplicateLogin),
+      ("disconnect.timeout", DisconnectKind::TimedOut),
+      ("multiplayer.disconnect.unsigned_chat", DisconnectKind::SecureChatRequired),
+      ("disconnect.spam", DisconnectKind::Other),
+    ];
+    for (key, kind) in cases {
+      assert_eq!(classify(json!({ "translate": key })), kind, "{}", key);
+    }
+  }
+
+  #[test]
+  fn classifies_bans_with_a_reason() {
+    let reason = json!({
+      "translate": "multiplayer.disconnect.banned.reason",
+      "with": ["Griefing"],
+    });
+    assert_eq!(classify(reason), DisconnectKind::Banned);
+    // wrapped by a plugin, and the key must not be mistaken for a longer one
+    let wrapped = json!({
+      "text": "",
+      "extra": [{ "translate": "multiplayer.disconnect.banned_ip.reason" }],
+    });
+    assert_eq!(classify(wrapped), DisconnectKind::Banned);
+    let other = json!({ "translate": "multiplayer.disconnect.bannedfoo" });
+    assert_eq!(classify(other), DisconnectKind::Other);
+  }
+
+  #[test]
+  fn falls_back_to_the_text() {
+    assert_eq!(
+      classify(json!("You are not white-listed on this server!")),
+      DisconnectKind::NotWhitelisted
+    );
+    assert_eq!(classify(json!({ "text": "Server closed" })), DisconnectKind::ServerClosed);
+    assert_eq!(classify(json!("Kicked by an operator.")), DisconnectKind::Other);
+    // an unknown key whose text still says what happened
+    let unknown = json!({ "translate": "proxy.kick", "extra": [" Connection timed out"] });
+    assert_eq!(classify(unknown), DisconnectKind::TimedOut);
+  }
+
+  #[test]
+  fn stops_reconnecting_after_a_ban() {
+    let reason = json!({
+      "translate": "multiplayer.disconnect.banned.reason",
+      "with": ["Griefing"],
+    });
+    let kicked = Kicked::new(
+      Some(&Component::from_json(&reason)),
+      &format::Component::Text(format::TextComponent::new("")),
+    );
+    assert_eq!(kicked.kind, DisconnectKind::Banned);
+    assert!(kicked.reason.contains("Griefing"));
+    assert!(!kicked.kind.should_retry());
+    let kinds = [
+      DisconnectKind::ServerFull,
+      DisconnectKind::ServerClosed,
+      DisconnectKind::TimedOut,
+    ];
+    for kind in kinds {
+      assert!(kind.should_retry(), "{:?}", kind);
+    }
+  }
+}
@@ -0,0 +1,413 @@
+pub mod codec;
+pub mod disconnect;
+pub mod outbound;
+pub mod player_info;
+pub mod queue;
+pub mod signed_chat;
+pub mod state;
+pub mod tablist;
+pub mod throttle;
+
+use std::{str::FromStr, sync::Arc};
+
+use color_eyre::eyre;
+use futures::StreamExt;
+use rand::Rng;
+use steven_protocol::protocol::{self, forge, mojang, packet, packet::Packet};
+use tracing::{debug, info, trace, warn};
+
+use self::{
+  codec::{Connection, Inbound, PacketReader, PacketWriter},
+  disconnect::Kicked,
+  outbound::Outbound,
+  queue::QueueMetrics,
+  state::{ConnState, Step},
+};
+use crate::config::CONFIG;
+
+pub(crate) const TARGET: &str = "mesagisto::game";
+
+#[derive(Debug)]
+pub struct Client {
+  pub default_protocol_version: i32,
+  pub profile: protocol::mojang::Profile,
+}
+
+impl Client {
+  pub fn new(default_protocol_version: i32, profile: protocol::mojang::Profile) -> Self {
+    Self {
+      default_protocol_version,
+      profile,
+    }
+  }
+
+  pub async fn connect_to(&self, address: &str) -> eyre::Result<Server> {
+    // the status ping is short lived, so the blocking Conn is fine here
+    let ping = {
+      let address = address.to_owned();
+      let version = self.default_protocol_version;
+      tokio::task::spawn_blocking(move || {
+        protocol::Conn::new(&address, version).and_then(|conn| conn.do_status())
+      })
+      .await?
+    };
+    let (protocol_version, forge_mods, fml_network_version) = match ping {
+      Ok(res) => {
+        info!(
+          target: TARGET,
+          "Detected server protocol version {}", res.0.version.protocol
+        );
+        (
+          res.0.version.protocol,
+          res.0.forge_mods,
+          res.0.fml_network_version,
+        )
+      }
+      Err(err) => {
+        warn!(
+          target: TARGET,
+          "Error pinging server {} to get protocol version: {:?}, fallback to {}",
+          address,
+          err,
+          self.default_protocol_version
+        );
+        (self.default_protocol_version, vec![], None)
+      }
+    };
+
+    let address = address.to_owned();
+    let server = Server::connect(
+      &self.profile,
+      &address,
+      protocol_version,
+      forge_mods,
+      fml_network_version,
+    )
+    .await?;
+    Ok(server)
+  }
+}
+
+pub struct Server {
+  pub protocol_version: i32,
+  pub uuid: protocol::UUID,
+  pub state: Arc<ConnState>,
+  pub conn: Option<PacketWriter>,
+  pub read_queue: Option<tokio::sync::mpsc::Receiver<Result<Inbound, protocol::Error>>>,
+  pub read_metrics: Arc<QueueMetrics>,
+}
+impl Server {
+  pub async fn connect(
+    profile: &mojang::Profile,
+    address: &str,
+    protocol_version: i32,
+    forge_mods: Vec<forge::ForgeMod>,
+    fml_network_version: Option<i64>,
+  ) -> eyre::Result<Server> {
+    let mut conn = Connection::connect(address, protocol_version).await?;
+    let state = Arc::new(ConnState::default());
+
+    let tag = match fml_network_version {
+      Some(1) => "\0FML\0",
+      Some(2) => "\0FML2\0",
+      None => "",
+      _ => panic!("unsupported FML network version: {:?}", fml_network_version),
+    };
+
+    let host = conn.host.clone() + tag;
+    let port = conn.port;
+
+    trace!("writing pkt");
+    conn
+      .write_packet(protocol::packet::handshake::serverbound::Handshake {
+        protocol_version: protocol::VarInt(protocol_version),
+        host,
+        port,
+        next: protocol::VarInt(2),
+      })
+      .await?;
+    trace!("writing pkt success");
+    state.advance(Step::Login)?;
+    conn.set_state(protocol::State::Login);
+    conn
+      .write_packet(Outbound::login_start(
+        profile.username.clone(),
+        uuid::Uuid::parse_str(&profile.id).ok(),
+      ))
+      .await?;
+
+    let (server_id, public_key, verify_token);
+    loop {
+      let Inbound { packet, chat } = conn.read_inbound().await?;
+      match packet {
+        protocol::packet::Packet::SetInitialCompression(val) => {
+          state.advance(Step::Compression)?;
+          conn.set_compression(val.threshold.0);
+        }
+        Packet::EncryptionRequest(val) => {
+          state.advance(Step::Encryption)?;
+          server_id = val.server_id;
+          public_key = val.public_key.data;
+          verify_token = val.verify_token.data;
+          break;
+        }
+        Packet::EncryptionRequest_i16(val) => {
+          state.advance(Step::Encryption)?;
+          server_id = val.server_id;
+          public_key = val.public_key.data;
+          verify_token = val.verify_token.data;
+          break;
+        }
+        Packet::LoginSuccess_String(val) => {
+          warn!(target: TARGET, "Server is running in offline mode");
+          debug!(target: TARGET, "Login: {} {}", val.username, val.uuid);
+          state.advance(Step::Play)?;
+          conn.set_state(protocol::State::Play);
+          let (read, write) = conn.into_split();
+          return Ok(Server::new(
+            protocol_version,
+            forge_mods,
+            protocol::UUID::from_str(&val.uuid).unwrap(),
+            state,
+            read,
+            write,
+          ));
+        }
+        Packet::LoginSuccess_UUID(val) => {
+          warn!(target: TARGET, "Server is running in offline mode");
+          debug!(target: TARGET, "Login: {} {:?}", val.username, val.uuid);
+          state.advance(Step::Play)?;
+          conn.set_state(protocol::State::Play);
+          let (read, write) = conn.into_split();
+          return Ok(Server::new(
+            protocol_version,
+            forge_mods,
+            val.uuid,
+            state,
+            read,
+            write,
+          ));
+        }
+        Packet::LoginDisconnect(val) => {
+          return Err(Kicked::new(chat.as_ref(), &val.reason).into());
+        }
+        val => eyre::bail!("Wrong packet 1: {:?}", val),
+      };
+    }
+
+    let mut shared = [0; 16];
+    rand::thread_rng().fill(&mut shared);
+
+    let shared_e = rsa_public_encrypt_pkcs1::encrypt(&public_key, &shared).unwrap();
+    let token_e = rsa_public_encrypt_pkcs1::encrypt(&public_key, &verify_token).unwrap();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+      if let Err(err) = profile.join_server(&server_id, &shared, &public_key).await {
+        warn!(
+          target: TARGET,
+          "Session server rejected the join request: {:?}", err
+        );
+        // a network error says nothing about the session
+        if crate::login::is_invalid_session(&err) {
+          crate::login::invalidate_session(profile);
+        }
+        return Err(err.into());
+      }
+    }
+
+    if protocol_version >= 47 {
+      conn
+        .write_packet(protocol::packet::login::serverbound::EncryptionResponse {
+          shared_secret: protocol::LenPrefixedBytes::new(shared_e),
+          verify_token: protocol::LenPrefixedBytes::new(token_e),
+        })
+        .await?;
+    } else {
+      conn
+        .write_packet(
+          protocol::packet::login::serverbound::EncryptionResponse_i16 {
+            shared_secret: protocol::LenPrefixedBytes::new(shared_e),
+            verify_token: protocol::LenPrefixedBytes::new(token_e),
+          },
+        )
+        .await?;
+    }
+
+    conn.enable_encryption(&shared);
+
+    let uuid;
+    let compression_threshold = conn.compression_threshold();
+    loop {
+      let Inbound { packet, chat } = conn.read_inbound().await?;
+      match packet {
+        Packet::SetInitialCompression(val) => {
+          state.advance(Step::Compression)?;
+          trace!(
+            target: TARGET,
+            "step8 S->C Set Compression (Optional, enables compression)"
+          );
+          conn.set_compression(val.threshold.0);
+        }
+        Packet::LoginSuccess_String(val) => {
+          state.advance(Step::Play)?;
+          trace!(target: TARGET, "step9 S->C Login Success");
+          debug!(target: TARGET, "Login: {} {}", val.username, val.uuid);
+          uuid = protocol::UUID::from_str(&val.uuid).unwrap();
+          conn.set_state(protocol::State::Play);
+          break;
+        }
+        Packet::LoginSuccess_UUID(val) => {
+          state.advance(Step::Play)?;
+          trace!(target: TARGET, "step9 S->C Login Success");
+          debug!(target: TARGET, "Login: {} {:?}", val.username, val.uuid);
+          uuid = val.uuid;
+          conn.set_state(protocol::State::Play);
+          break;
+        }
+        Packet::LoginDisconnect(val) => {
+          return Err(Kicked::new(chat.as_ref(), &val.reason).into());
+        }
+        Packet::LoginPluginRequest(req) => match req.channel.as_ref() {
+          "fml:loginwrapper" => {
+            let mut cursor = std::io::Cursor::new(req.data);
+            let channel: String = protocol::Serializable::read_from(&mut cursor)?;
+
+            let (id, mut data) =
+              protocol::Conn::read_raw_packet_from(&mut cursor, compression_threshold)?;
+
+            match channel.as_ref() {
+              "fml:handshake" => {
+                let packet = forge::fml2::FmlHandshake::packet_by_id(id, &mut data)?;
+                use forge::fml2::FmlHandshake::*;
+                match packet {
+                  ModList {
+                    mod_names,
+                    channels,
+                    registries,
+                  } => {
+                    info!(
+                      target: TARGET,
+                      "ModList mod_names={:?} channels={:?} registries={:?}",
+                      mod_names,
+                      channels,
+                      registries
+                    );
+                    conn
+                      .write_fml2_handshake_plugin_message(
+                        req.message_id,
+                        &ModListReply {
+                          mod_names,
+                          channels,
+                          registries,
+                        },
+                      )
+                      .await?;
+                  }
+                  ServerRegistry {
+                    name,
+                    snapshot_present: _,
+                    snapshot: _,
+                  } => {
+                    info!(target: TARGET, "ServerRegistry {:?}", name);
+                    conn
+                      .write_fml2_handshake_plugin_message(
+                        req.message_id,
+                        &Acknowledgement,
+                      )
+                      .await?;
+                  }
+                  ConfigurationData { filename, contents } => {
+                    info!(
+                      target: TARGET,
+                      "ConfigurationData filename={:?} contents={}",
+                      filename,
+                      String::from_utf8_lossy(&contents)
+                    );
+                    conn
+                      .write_fml2_handshake_plugin_message(
+                        req.message_id,
+                        &Acknowledgement,
+                      )
+                      .await?;
+                  }
+                  _ => unimplemented!(),
+                }
+              }
+              _ => panic!(
+                "unknown LoginPluginRequest fml:loginwrapper channel: {:?}",
+                channel
+              ),
+            }
+          }
+          _ => panic!("unsupported LoginPluginRequest channel: {:?}", req.channel),
+        },
+        val => eyre::bail!("Wrong packet 2: {:?}", val),
+      }
+    }
+
+    let (read, write) = conn.into_split();
+
+    Ok(Server::new(
+      protocol_version,
+      forge_mods,
+      uuid,
+      state,
+      read,
+      write,
+    ))
+  }
+
+  fn spawn_reader_async(
+    mut read: PacketReader,
+  ) -> (
+    tokio::sync::mpsc::Receiver<Result<Inbound, protocol::Error>>,
+    Arc<QueueMetrics>,
+  ) {
+    let (tx, rx) = queue::channel("read", CONFIG.queue.read_capacity);
+    let metrics = tx.metrics();
+    tokio::spawn(async move {
+      loop {
+        // stop reading as soon as nobody listens, this also drops the socket
+        let pck = tokio::select! {
+          _ = tx.closed() => return,
+          pck = read.next() => pck,
+        };
+        let pck = match pck {
+          Some(pck) => pck,
+          None => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        };
+        let was_error = pck.is_err();
+
+        if tx.send(pck).await.is_err() {
+          return;
+        }
+        if was_error {
+          return;
+        }
+      }
+    });
+    (rx, metrics)
+  }
+
+  fn new(
+    protocol_version: i32,
+    _forge_mods: Vec<forge::ForgeMod>,
+    uuid: protocol::UUID,
+    state: Arc<ConnState>,
+    read: PacketReader,
+    write: PacketWriter,
+  ) -> Server {
+    let (read_queue, read_metrics) = Self::spawn_reader_async(read);
+    Server {
+      protocol_version,
+      uuid,
+      state,
+      conn: Some(write),
+      // forge_mods,
+      read_queue: Some(read_queue),
+      read_metrics,
+      // disconnect_reason: None,
+    }
+  }
+}
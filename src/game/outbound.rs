@@ -0,0 +1,375 @@
+use std::{any::TypeId, collections::HashMap, fmt, io};
+
+use once_cell::sync::Lazy;
+use steven_protocol::protocol::{self, packet, PacketType, State};
+
+use super::{queue::Droppable, signed_chat};
+
+trait ErasedServerbound: fmt::Debug + Send {
+  /// The state the packet is sent in, None when it is not a serverbound packet
+  /// of `steven_protocol`.
+  fn declared(&self) -> Option<State>;
+  fn packet_id(&self, protocol_version: i32) -> i32;
+  fn write_to(&self, protocol_version: i32, buf: &mut Vec<u8>) -> Result<(), protocol::Error>;
+}
+
+impl<P: PacketType + fmt::Debug + Send + 'static> ErasedServerbound for P {
+  fn declared(&self) -> Option<State> {
+    DECLARED.get(&TypeId::of::<P>()).copied()
+  }
+
+  fn packet_id(&self, protocol_version: i32) -> i32 {
+    PacketType::packet_id(self, protocol_version)
+  }
+
+  fn write_to(&self, _protocol_version: i32, buf: &mut Vec<u8>) -> Result<(), protocol::Error> {
+    PacketType::write(self, buf)
+  }
+}
+
+/// A chat message, or a command when it starts with `/`. Since 1.19 both are
+/// packets `steven_protocol` does not know and which are sent unsigned.
+#[derive(Debug)]
+struct Chat(String);
+
+impl Chat {
+  fn legacy(&self) -> packet::play::serverbound::ChatMessage {
+    packet::play::serverbound::ChatMessage {
+      message: self.0.clone(),
+    }
+  }
+}
+
+impl ErasedServerbound for Chat {
+  fn declared(&self) -> Option<State> {
+    Some(State::Play)
+  }
+
+  fn packet_id(&self, protocol_version: i32) -> i32 {
+    match signed_chat::chat_packet_id(protocol_version, self.0.starts_with('/')) {
+      Some(id) => id,
+      None => PacketType::packet_id(&self.legacy(), protocol_version),
+    }
+  }
+
+  fn write_to(&self, protocol_version: i32, buf: &mut Vec<u8>) -> Result<(), protocol::Error> {
+    match signed_chat::chat_packet_id(protocol_version, false) {
+      Some(_) => signed_chat::write_chat(protocol_version, &self.0, buf),
+      None => PacketType::write(&self.legacy(), buf),
+    }
+  }
+}
+
+/// The `LoginStart` of every version, which since 1.19 carries more than the
+/// name `steven_protocol` knows.
+#[derive(Debug)]
+struct LoginStart {
+  username: String,
+  uuid: Option<uuid::Uuid>,
+}
+
+impl LoginStart {
+  fn legacy(&self) -> packet::login::serverbound::LoginStart {
+    packet::login::serverbound::LoginStart {
+      username: self.username.clone(),
+    }
+  }
+}
+
+impl ErasedServerbound for LoginStart {
+  fn declared(&self) -> Option<State> {
+    Some(State::Login)
+  }
+
+  fn packet_id(&self, protocol_version: i32) -> i32 {
+    if signed_chat::has_own_login_start(protocol_version) {
+      signed_chat::LOGIN_START_ID
+    } else {
+      PacketType::packet_id(&self.legacy(), protocol_version)
+    }
+  }
+
+  fn write_to(&self, protocol_version: i32, buf: &mut Vec<u8>) -> Result<(), protocol::Error> {
+    if signed_chat::has_own_login_start(protocol_version) {
+      signed_chat::write_login_start(protocol_version, &self.username, self.uuid, buf)
+    } else {
+      PacketType::write(&self.legacy(), buf)
+    }
+  }
+}
+
+/// Any serverbound packet, as accepted by the write queue.
+#[derive(Debug)]
+pub struct Outbound(Box<dyn ErasedServerbound>);
+
+impl Outbound {
+  /// A chat line, or a command when it starts with `/`, in whatever packet
+  /// the protocol version uses for it.
+  pub fn chat(text: impl Into<String>) -> Self {
+    Outbound(Box::new(Chat(text.into())))
+  }
+
+  /// The `LoginStart` of the protocol version, with the uuid where it takes
+  /// one.
+  pub fn login_start(username: impl Into<String>, uuid: Option<uuid::Uuid>) -> Self {
+    Outbound(Box::new(LoginStart {
+      username: username.into(),
+      uuid,
+    }))
+  }
+
+  /// Fails unless the packet is a serverbound one of `state`, in which case
+  /// nothing is written and the connection stays usable.
+  pub fn check(&self, state: State) -> Result<(), WriteError> {
+    let expected = self
+      .0
+      .declared()
+      .ok_or_else(|| WriteError::Undeclared(format!("{:?}", self)))?;
+    if expected != state {
+      return Err(WriteError::WrongState {
+        packet: format!("{:?}", self),
+        expected,
+        actual: state,
+      });
+    }
+    Ok(())
+  }
+
+  pub fn packet_id(&self, protocol_version: i32) -> i32 {
+    self.0.packet_id(protocol_version)
+  }
+
+  pub fn write_to(&self, protocol_version: i32, buf: &mut Vec<u8>) -> Result<(), protocol::Error> {
+    self.0.write_to(protocol_version, buf)
+  }
+}
+
+impl<P: PacketType + fmt::Debug + Send + 'static> From<P> for Outbound {
+  fn from(packet: P) -> Self {
+    Outbound(Box::new(packet))
+  }
+}
+
+// every serverbound packet of `steven_protocol`, by the module of the state
+// it is sent in. `writes_every_serverbound_packet` fails on any the protocol
+// decodes that is missing here
+macro_rules! serverbound_packets {
+  ($then:ident) => {
+    $then! {
+      handshake { Handshake }
+      status { StatusRequest, StatusPing }
+      login { LoginStart, EncryptionResponse, EncryptionResponse_i16, LoginPluginResponse }
+      play {
+        TeleportConfirm, QueryBlockNBT, SetDifficulty, TabComplete, TabComplete_NoAssume,
+        TabComplete_NoAssume_NoTarget, ChatMessage, ClientStatus, ClientStatus_u8,
+        ClientSettings_Filtering, ClientSettings, ClientSettings_u8, ClientSettings_u8_Handsfree,
+        ClientSettings_u8_Handsfree_Difficulty, ConfirmTransactionServerbound, EnchantItem,
+        ClickWindowButton, ClickWindow_State, ClickWindow, ClickWindow_u8, CloseWindow,
+        PluginMessageServerbound, PluginMessageServerbound_i16, EditBook_Pages, EditBook_Item,
+        QueryEntityNBT, UseEntity_Sneakflag, UseEntity_Hand, UseEntity_Handsfree,
+        UseEntity_Handsfree_i32, GenerateStructure, KeepAliveServerbound_i64,
+        KeepAliveServerbound_VarInt, KeepAliveServerbound_i32, LockDifficulty, PlayerPosition,
+        PlayerPosition_HeadY, PlayerPositionLook, PlayerPositionLook_HeadY, PlayerLook, Player,
+        VehicleMove, SteerBoat, PickItem, CraftRecipeRequest, ClientAbilities_f32,
+        ClientAbilities_u8, PlayerDigging, PlayerDigging_u8, PlayerDigging_u8_u8y, PlayerAction,
+        PlayerAction_i32, SteerVehicle, SteerVehicle_jump_unmount, WindowPong, CraftingBookData,
+        SetDisplayedRecipe, SetRecipeBookState, NameItem, ResourcePackStatus,
+        ResourcePackStatus_hash, AdvancementTab, SelectTrade, SetBeaconEffect, HeldItemChange,
+        UpdateCommandBlock, UpdateCommandBlockMinecart, CreativeInventoryAction,
+        UpdateJigsawBlock_Joint, UpdateJigsawBlock_Type, UpdateStructureBlock, SetSign,
+        SetSign_i16y, ArmSwing, ArmSwing_Handsfree, ArmSwing_Handsfree_ID, SpectateTeleport,
+        PlayerBlockPlacement_f32, PlayerBlockPlacement_u8, PlayerBlockPlacement_u8_Item,
+        PlayerBlockPlacement_u8_Item_u8y, PlayerBlockPlacement_insideblock, UseItem,
+      }
+    }
+  };
+}
+
+macro_rules! state_of {
+  (handshake) => {
+    State::Handshaking
+  };
+  (status) => {
+    State::Status
+  };
+  (login) => {
+    State::Login
+  };
+  (play) => {
+    State::Play
+  };
+}
+
+macro_rules! declared {
+  ($($module:ident { $($name:ident),* $(,)? })*) => {
+    HashMap::from([
+      $($((TypeId::of::<packet::$module::serverbound::$name>(), state_of!($module)),)*)*
+    ])
+  };
+}
+
+static DECLARED: Lazy<HashMap<TypeId, State>> = Lazy::new(|| serverbound_packets!(declared));
+
+// the bot only sends what the server expects from it
+impl Droppable for Outbound {
+  fn is_droppable(&self) -> bool {
+    false
+  }
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+  /// A type which is not one of the serverbound packets of `steven_protocol`,
+  /// such as a clientbound one.
+  Undeclared(String),
+  /// The packet belongs to another protocol state than the connection's.
+  WrongState {
+    packet: String,
+    expected: State,
+    actual: State,
+  },
+  Protocol(protocol::Error),
+}
+
+impl fmt::Display for WriteError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WriteError::Undeclared(packet) => write!(f, "cannot tell where {} is sent", packet),
+      WriteError::WrongState {
+        packet,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "cannot send {} in {:?} state, it belongs to {:?}",
+        packet, actual, expected
+      ),
+      WriteError::Protocol(err) => write!(f, "{:?}", err),
+    }
+  }
+}
+
+impl std::error::Error for WriteError {}
+
+impl From<protocol::Error> for WriteError {
+  fn from(err: protocol::Error) -> Self {
+    WriteError::Protocol(err)
+  }
+}
+
+impl From<io::Error> for WriteError {
+  fn from(err: io::Error) -> Self {
+    WriteError::Protocol(err.into())
+  }
+}
+
+impl WriteError {
+  /// Whether the packet was refused before anything was written.
+  pub fn is_refused(&self) -> bool {
+    !matches!(self, WriteError::Protocol(_))
+  }
+}
+
+impl From<WriteError> for protocol::Error {
+  fn from(err: WriteError) -> Self {
+    match err {
+      WriteError::Protocol(err) => err,
+      err => protocol::Error::Err(err.to_string()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use bytes::BytesMut;
+  use steven_protocol::protocol::{Direction, SUPPORTED_PROTOCOLS};
+  use tokio_util::codec::Encoder;
+
+  use super::*;
+  use crate::game::codec::PacketCodec;
+
+  type Write = fn(i32) -> Result<State, WriteError>;
+
+  // writes the packet in the state it is declared for, which is returned
+  fn write<P>(protocol_version: i32) -> Result<State, WriteError>
+  where
+    P: PacketType + Default + fmt::Debug + Send + 'static,
+  {
+    let outbound = Outbound::from(P::default());
+    let state = outbound.0.declared().expect("declared by steven_protocol");
+    let mut codec = PacketCodec::new(protocol_version);
+    codec.set_state(state);
+    codec.encode(outbound, &mut BytesMut::new())?;
+    Ok(state)
+  }
+
+  macro_rules! writers {
+    ($($module:ident { $($name:ident),* $(,)? })*) => {
+      HashMap::from([
+        $($((stringify!($name), write::<packet::$module::serverbound::$name> as Write),)*)*
+      ])
+    };
+  }
+
+  // by the name of their `Packet` variant
+  fn writers() -> HashMap<&'static str, Write> {
+    serverbound_packets!(writers)
+  }
+
+  // every serverbound packet `steven_protocol` decodes in some version, with
+  // the version and state it was found in
+  fn decoded_serverbound() -> Vec<(String, i32, State)> {
+    let mut packets = Vec::new();
+    for &version in SUPPORTED_PROTOCOLS.iter() {
+      for state in [State::Handshaking, State::Status, State::Login, State::Play] {
+        for id in 0..0x80 {
+          // zeros are an empty string, a false, a zero or an empty slot
+          let mut body = Cursor::new(vec![0; 1024]);
+          let decoded = packet::packet_by_id(version, state, Direction::Serverbound, id, &mut body);
+          if let Ok(Some(packet)) = decoded {
+            let name = format!("{:?}", packet);
+            let name = name.split('(').next().unwrap().to_owned();
+            packets.push((name, version, state));
+          }
+        }
+      }
+    }
+    packets
+  }
+
+  #[test]
+  fn writes_every_serverbound_packet() {
+    let writers = writers();
+    let packets = decoded_serverbound();
+    assert!(!packets.is_empty());
+    for (name, version, state) in packets {
+      let write = writers
+        .get(name.as_str())
+        .unwrap_or_else(|| panic!("{} is missing from serverbound_packets", name));
+      match write(version) {
+        Ok(declared) => assert_eq!(declared, state, "{} is declared in the wrong state", name),
+        Err(err) => panic!("failed to write {} for {}: {}", name, version, err),
+      }
+    }
+  }
+
+  #[test]
+  fn refuses_clientbound_and_wrong_state_packets() {
+    let keep_alive = Outbound::from(packet::play::clientbound::KeepAliveClientbound_i64::default());
+    assert!(matches!(keep_alive.check(State::Play), Err(WriteError::Undeclared(_))));
+
+    let login = Outbound::from(packet::login::serverbound::LoginStart::default());
+    assert!(matches!(
+      login.check(State::Play),
+      Err(WriteError::WrongState {
+        expected: State::Login,
+        actual: State::Play,
+        ..
+      })
+    ));
+    assert!(login.check(State::Login).is_ok());
+    assert!(Outbound::chat("hello").check(State::Play).is_ok());
+  }
+}
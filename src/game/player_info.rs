@@ -0,0 +1,228 @@
+use std::io;
+
+use steven_protocol::{
+  format,
+  protocol::{
+    self,
+    packet::{play::clientbound::PlayerInfo, Packet, PlayerDetail, PlayerInfoData},
+    LenPrefixed, Serializable, VarInt, UUID,
+  },
+};
+
+use super::{
+  codec::Inbound,
+  signed_chat::{read_component, read_optional, skip, skip_byte_array, Decoded},
+};
+use crate::chat::Lang;
+
+// The tab list of 1.19.3 up to 1.20.1, which `steven_protocol` does not know.
+// The packets are turned into the `PlayerInfo` of 1.16 so the tab list sees no
+// difference. Vanilla adds a player with every action at once and changes one
+// thing at a time, which is what `PlayerInfo` can express.
+
+// the actions of `PlayerInfoUpdate`, in the order their fields follow the uuid
+const ADD_PLAYER: u8 = 0x01;
+const INITIALIZE_CHAT: u8 = 0x02;
+const UPDATE_GAME_MODE: u8 = 0x04;
+const UPDATE_LISTED: u8 = 0x08;
+const UPDATE_LATENCY: u8 = 0x10;
+const UPDATE_DISPLAY_NAME: u8 = 0x20;
+
+// the ids of `PlayerInfoRemove` and `PlayerInfoUpdate`
+fn ids(protocol_version: i32) -> Option<(i32, i32)> {
+  match protocol_version {
+    // 1.19.3
+    761 => Some((0x35, 0x36)),
+    // 1.19.4 to 1.20.1
+    762 | 763 => Some((0x39, 0x3A)),
+    _ => None,
+  }
+}
+
+/// Decodes the tab list packets of 1.19.3 and later, `body` starts after the
+/// id.
+pub fn decode(
+  protocol_version: i32,
+  id: i32,
+  body: &mut io::Cursor<Vec<u8>>,
+) -> Result<Decoded, protocol::Error> {
+  let (remove, update) = match ids(protocol_version) {
+    Some(ids) => ids,
+    None => return Ok(Decoded::Other),
+  };
+  let inner = if id == remove {
+    let mut details = Vec::new();
+    for _ in 0..VarInt::read_from(body)?.0 {
+      let uuid = UUID::read_from(body)?;
+      details.push(PlayerDetail::Remove { uuid });
+    }
+    PlayerInfoData::Remove(LenPrefixed::new(details))
+  } else if id == update {
+    match read_update(body)? {
+      Some(inner) => inner,
+      None => return Ok(Decoded::Skip),
+    }
+  } else {
+    return Ok(Decoded::Other);
+  };
+  Ok(Decoded::Packet(Inbound {
+    packet: Packet::PlayerInfo(PlayerInfo { inner }),
+    chat: None,
+  }))
+}
+
+// None when only the chat session or whether the player is listed changed
+fn read_update(r: &mut io::Cursor<Vec<u8>>) -> Result<Option<PlayerInfoData>, protocol::Error> {
+  let actions = u8::read_from(r)?;
+  let has = |action: u8| actions & action != 0;
+  let mut details = Vec::new();
+  for _ in 0..VarInt::read_from(r)?.0 {
+    let uuid = UUID::read_from(r)?;
+    let mut name = String::new();
+    if has(ADD_PLAYER) {
+      name = String::read_from(r)?;
+      // the skin and cape, of no use to the bot
+      for _ in 0..VarInt::read_from(r)?.0 {
+        String::read_from(r)?;
+        String::read_from(r)?;
+        read_optional(r, |r| String::read_from(r).map(drop))?;
+      }
+    }
+    if has(INITIALIZE_CHAT) {
+      read_optional(r, |r| {
+        UUID::read_from(r)?;
+        // the expiry, public key and its signature
+        skip(r, 8)?;
+        skip_byte_array(r)?;
+        skip_byte_array(r)
+      })?;
+    }
+    let gamemode = if has(UPDATE_GAME_MODE) {
+      VarInt::read_from(r)?
+    } else {
+      VarInt(0)
+    };
+    if has(UPDATE_LISTED) {
+      bool::read_from(r)?;
+    }
+    let ping = if has(UPDATE_LATENCY) {
+      VarInt::read_from(r)?
+    } else {
+      VarInt(0)
+    };
+    let display = if has(UPDATE_DISPLAY_NAME) {
+      read_optional(r, read_component)?.map(|display| {
+        format::Component::Text(format::TextComponent::new(
+          &display.to_plain(Lang::current()),
+        ))
+      })
+    } else {
+      None
+    };
+    details.push(if has(ADD_PLAYER) {
+      PlayerDetail::Add {
+        uuid,
+        name,
+        properties: Vec::new(),
+        gamemode,
+        ping,
+        display,
+      }
+    } else if has(UPDATE_DISPLAY_NAME) {
+      PlayerDetail::UpdateDisplayName { uuid, display }
+    } else if has(UPDATE_LATENCY) {
+      PlayerDetail::UpdateLatency { uuid, ping }
+    } else {
+      PlayerDetail::UpdateGamemode { uuid, gamemode }
+    });
+  }
+  let details = LenPrefixed::new(details);
+  let inner = if has(ADD_PLAYER) {
+    PlayerInfoData::Add(details)
+  } else if has(UPDATE_DISPLAY_NAME) {
+    PlayerInfoData::UpdateDisplayName(details)
+  } else if has(UPDATE_LATENCY) {
+    PlayerInfoData::UpdateLatency(details)
+  } else if has(UPDATE_GAME_MODE) {
+    PlayerInfoData::UpdateGamemode(details)
+  } else {
+    return Ok(None);
+  };
+  Ok(Some(inner))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::tablist::{GameMode, TabEvent, TabList};
+
+  fn uuid() -> UUID {
+    UUID::read_from(&mut &[7u8; 16][..]).unwrap()
+  }
+
+  fn packet(id: i32, write: impl FnOnce(&mut Vec<u8>)) -> Packet {
+    let mut body = Vec::new();
+    write(&mut body);
+    match decode(763, id, &mut io::Cursor::new(body)).unwrap() {
+      Decoded::Packet(inbound) => inbound.packet,
+      _ => panic!("0x{:X} was not decoded", id),
+    }
+  }
+
+  #[test]
+  fn tracks_players_of_1_19_3() {
+    let tablist = TabList::new(None, String::from("Bot"));
+    let joined = packet(0x3A, |b| {
+      (ADD_PLAYER | INITIALIZE_CHAT | UPDATE_GAME_MODE | UPDATE_LISTED | UPDATE_LATENCY)
+        .write_to(b)
+        .unwrap();
+      VarInt(1).write_to(b).unwrap();
+      uuid().write_to(b).unwrap();
+      String::from("Steve").write_to(b).unwrap();
+      VarInt(1).write_to(b).unwrap();
+      for field in ["textures", "skin", "signature"] {
+        String::from(field).write_to(b).unwrap();
+        if field == "skin" {
+          true.write_to(b).unwrap();
+        }
+      }
+      // no chat session
+      false.write_to(b).unwrap();
+      VarInt(1).write_to(b).unwrap();
+      true.write_to(b).unwrap();
+      VarInt(42).write_to(b).unwrap();
+    });
+    let events = tablist.apply(&joined);
+    assert!(matches!(&events[..], [TabEvent::Joined(player)] if player.name == "Steve"));
+    let players = tablist.players();
+    assert_eq!(players[0].game_mode, Some(GameMode::Creative));
+    assert_eq!(players[0].latency, 42);
+
+    let renamed = packet(0x3A, |b| {
+      UPDATE_DISPLAY_NAME.write_to(b).unwrap();
+      VarInt(1).write_to(b).unwrap();
+      uuid().write_to(b).unwrap();
+      true.write_to(b).unwrap();
+      String::from(r#"{"text":"[Admin] Steve"}"#).write_to(b).unwrap();
+    });
+    assert!(tablist.apply(&renamed).is_empty());
+    assert_eq!(tablist.players()[0].shown_name(), "[Admin] Steve");
+
+    let left = packet(0x39, |b| {
+      VarInt(1).write_to(b).unwrap();
+      uuid().write_to(b).unwrap();
+    });
+    let events = tablist.apply(&left);
+    assert!(matches!(&events[..], [TabEvent::Left(player)] if player.name == "Steve"));
+    assert!(tablist.players().is_empty());
+  }
+
+  #[test]
+  fn leaves_other_packets_and_versions_alone() {
+    let mut body = io::Cursor::new(vec![0]);
+    assert!(matches!(decode(763, 0x3B, &mut body).unwrap(), Decoded::Other));
+    assert!(matches!(decode(760, 0x3A, &mut body).unwrap(), Decoded::Other));
+    let mut listed = io::Cursor::new(vec![UPDATE_LISTED, 0]);
+    assert!(matches!(decode(763, 0x3A, &mut listed).unwrap(), Decoded::Skip));
+  }
+}
@@ -0,0 +1,267 @@
+use std::{
+  fmt,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+};
+
+use steven_protocol::protocol::{self, packet::Packet};
+use tokio::sync::mpsc::{
+  self,
+  error::{SendError, TrySendError},
+};
+use tracing::debug;
+
+use super::{codec::Inbound, TARGET};
+
+/// Whether an item may be discarded when its queue is full. Anything that
+/// the server or the bridge would miss must return false.
+pub trait Droppable {
+  fn is_droppable(&self) -> bool;
+}
+
+impl Droppable for Packet {
+  fn is_droppable(&self) -> bool {
+    use Packet::*;
+    // the bot neither renders the world nor tracks entities, and a newer
+    // movement or chunk packet supersedes a dropped one anyway
+    matches!(
+      self,
+      EntityMove_i16(_)
+        | EntityMove_i8(_)
+        | EntityMove_i8_i32_NoGround(_)
+        | EntityLookAndMove_i16(_)
+        | EntityLookAndMove_i8(_)
+        | EntityLookAndMove_i8_i32_NoGround(_)
+        | EntityLook_VarInt(_)
+        | EntityLook_i32_NoGround(_)
+        | EntityHeadLook(_)
+        | EntityHeadLook_i32(_)
+        | EntityTeleport_f64(_)
+        | EntityTeleport_i32(_)
+        | EntityTeleport_i32_i32_NoGround(_)
+        | EntityVelocity(_)
+        | EntityVelocity_i32(_)
+        | EntityMetadata(_)
+        | EntityMetadata_i32(_)
+        | EntityEquipment_Array(_)
+        | EntityEquipment_VarInt(_)
+        | EntityEquipment_u16(_)
+        | EntityEquipment_u16_i32(_)
+        | EntityProperties_VarIntVarInt(_)
+        | EntityProperties_VarInt(_)
+        | EntityProperties_i32(_)
+        | EntityStatus(_)
+        | Animation(_)
+        | ChunkData_Biomes3D_Bitmasks(_)
+        | ChunkData_Biomes3D_VarInt(_)
+        | ChunkData_Biomes3D_bool(_)
+        | ChunkData_Biomes3D(_)
+        | ChunkData_HeightMap(_)
+        | ChunkData(_)
+        | ChunkData_NoEntities(_)
+        | ChunkData_NoEntities_u16(_)
+        | ChunkData_17(_)
+        | ChunkDataBulk(_)
+        | ChunkDataBulk_17(_)
+        | ChunkUnload(_)
+        | UpdateLight_Arrays(_)
+        | UpdateLight_WithTrust(_)
+        | UpdateLight_NoTrust(_)
+        | BlockChange_VarInt(_)
+        | BlockChange_u8(_)
+        | MultiBlockChange_Packed(_)
+        | MultiBlockChange_VarInt(_)
+        | MultiBlockChange_u16(_)
+        | BlockBreakAnimation(_)
+        | BlockBreakAnimation_i32(_)
+        | Particle_f64(_)
+        | Particle_f32(_)
+        | Particle_VarIntArray(_)
+        | Particle_Named(_)
+        | SoundEffect(_)
+        | SoundEffect_u8(_)
+        | NamedSoundEffect(_)
+        | NamedSoundEffect_u8(_)
+        | NamedSoundEffect_u8_NoCategory(_)
+        | EntitySoundEffect(_)
+        | TimeUpdate(_)
+    )
+  }
+}
+
+impl Droppable for Result<Inbound, protocol::Error> {
+  fn is_droppable(&self) -> bool {
+    match self {
+      Ok(inbound) => inbound.packet.is_droppable(),
+      Err(_) => false,
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct QueueMetrics {
+  sent: AtomicU64,
+  dropped: AtomicU64,
+  backpressure: AtomicU64,
+  high_water: AtomicU64,
+}
+
+impl QueueMetrics {
+  pub fn sent(&self) -> u64 {
+    self.sent.load(Ordering::Relaxed)
+  }
+
+  /// Droppable items discarded because the queue was full.
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  /// How often a sender had to wait for room in the queue.
+  pub fn backpressure(&self) -> u64 {
+    self.backpressure.load(Ordering::Relaxed)
+  }
+
+  /// The most items the queue held at once.
+  pub fn high_water(&self) -> u64 {
+    self.high_water.load(Ordering::Relaxed)
+  }
+}
+
+impl fmt::Display for QueueMetrics {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "sent={} dropped={} backpressure={} high_water={}",
+      self.sent(),
+      self.dropped(),
+      self.backpressure(),
+      self.high_water()
+    )
+  }
+}
+
+/// A bounded queue which drops [Droppable] items when full and makes the
+/// sender wait for every other item.
+pub fn channel<T: Droppable>(
+  name: &'static str,
+  capacity: usize,
+) -> (QueueSender<T>, mpsc::Receiver<T>) {
+  let (tx, rx) = mpsc::channel(capacity);
+  let sender = QueueSender {
+    name,
+    capacity,
+    tx,
+    metrics: Default::default(),
+  };
+  (sender, rx)
+}
+
+#[derive(Debug)]
+pub struct QueueSender<T> {
+  name: &'static str,
+  capacity: usize,
+  tx: mpsc::Sender<T>,
+  metrics: Arc<QueueMetrics>,
+}
+
+impl<T> Clone for QueueSender<T> {
+  fn clone(&self) -> Self {
+    Self {
+      name: self.name,
+      capacity: self.capacity,
+      tx: self.tx.clone(),
+      metrics: self.metrics.clone(),
+    }
+  }
+}
+
+impl<T: Droppable> QueueSender<T> {
+  pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+    let result = match self.tx.try_send(item) {
+      Ok(()) => Ok(()),
+      Err(TrySendError::Closed(item)) => return Err(SendError(item)),
+      Err(TrySendError::Full(item)) if item.is_droppable() => {
+        let dropped = self.metrics.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        if dropped.is_power_of_two() {
+          debug!(
+            target: TARGET,
+            "{} queue is full, {} droppable packets discarded so far", self.name, dropped
+          );
+        }
+        return Ok(());
+      }
+      Err(TrySendError::Full(item)) => {
+        self.metrics.backpressure.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(item).await
+      }
+    };
+    if result.is_ok() {
+      self.metrics.sent.fetch_add(1, Ordering::Relaxed);
+      let len = self.capacity - self.tx.capacity();
+      self.metrics.high_water.fetch_max(len as u64, Ordering::Relaxed);
+    }
+    result
+  }
+
+  pub async fn closed(&self) {
+    self.tx.closed().await
+  }
+
+  pub fn metrics(&self) -> Arc<QueueMetrics> {
+    self.metrics.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, PartialEq, Eq)]
+  struct Item(u32, bool);
+
+  impl Droppable for Item {
+    fn is_droppable(&self) -> bool {
+      self.1
+    }
+  }
+
+  #[tokio::test]
+  async fn drops_droppable_items_when_full() {
+    let (tx, mut rx) = channel("test", 2);
+    tx.send(Item(1, false)).await.unwrap();
+    tx.send(Item(2, true)).await.unwrap();
+    // full, the droppable one goes without waiting
+    tx.send(Item(3, true)).await.unwrap();
+    assert_eq!(rx.recv().await, Some(Item(1, false)));
+    tx.send(Item(4, false)).await.unwrap();
+    assert_eq!(rx.recv().await, Some(Item(2, true)));
+    assert_eq!(rx.recv().await, Some(Item(4, false)));
+
+    let metrics = tx.metrics();
+    assert_eq!(metrics.sent(), 3);
+    assert_eq!(metrics.dropped(), 1);
+    assert_eq!(metrics.backpressure(), 0);
+    assert_eq!(metrics.high_water(), 2);
+  }
+
+  #[tokio::test]
+  async fn waits_for_room_for_other_items() {
+    let (tx, mut rx) = channel("test", 1);
+    tx.send(Item(1, false)).await.unwrap();
+    let send = tx.send(Item(2, false));
+    tokio::pin!(send);
+    assert!(futures::poll!(send.as_mut()).is_pending());
+    assert_eq!(tx.metrics().backpressure(), 1);
+
+    assert_eq!(rx.recv().await, Some(Item(1, false)));
+    send.await.unwrap();
+    assert_eq!(rx.recv().await, Some(Item(2, false)));
+
+    let metrics = tx.metrics();
+    assert_eq!(metrics.sent(), 2);
+    assert_eq!(metrics.dropped(), 0);
+    assert_eq!(metrics.high_water(), 1);
+  }
+}
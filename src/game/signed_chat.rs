@@ -0,0 +1,436 @@
+use std::io::{self, Read, Write};
+
+use steven_protocol::{
+  format,
+  protocol::{
+    self,
+    packet::{play::clientbound::ServerMessage_Sender, Packet},
+    Serializable, VarInt, UUID,
+  },
+};
+use tracing::warn;
+
+use super::{codec::Inbound, TARGET};
+use crate::chat::{component::Content, Component, Lang};
+
+// Chat of 1.19 up to 1.20.1, which `steven_protocol` does not know. The
+// clientbound packets are turned into the `ServerMessage_Sender` of 1.16 so
+// the handlers see no difference, and chat is always sent unsigned. The
+// `LoginStart` of these versions, without which the bot never gets to chat,
+// is written here as well.
+
+struct Ids {
+  player_chat: i32,
+  system_chat: i32,
+  disguised_chat: Option<i32>,
+  server_data: i32,
+  chat_command: i32,
+  chat_message: i32,
+}
+
+fn ids(protocol_version: i32) -> Option<Ids> {
+  let ids = match protocol_version {
+    // 1.19
+    759 => Ids {
+      player_chat: 0x30,
+      system_chat: 0x5F,
+      disguised_chat: None,
+      server_data: 0x3F,
+      chat_command: 0x03,
+      chat_message: 0x04,
+    },
+    // 1.19.1 and 1.19.2
+    760 => Ids {
+      player_chat: 0x33,
+      system_chat: 0x62,
+      disguised_chat: None,
+      server_data: 0x42,
+      chat_command: 0x04,
+      chat_message: 0x05,
+    },
+    // 1.19.3
+    761 => Ids {
+      player_chat: 0x31,
+      system_chat: 0x60,
+      disguised_chat: Some(0x18),
+      server_data: 0x41,
+      chat_command: 0x04,
+      chat_message: 0x05,
+    },
+    // 1.19.4 to 1.20.1
+    762 | 763 => Ids {
+      player_chat: 0x35,
+      system_chat: 0x64,
+      disguised_chat: Some(0x1C),
+      server_data: 0x45,
+      chat_command: 0x04,
+      chat_message: 0x05,
+    },
+    _ => return None,
+  };
+  Some(ids)
+}
+
+pub enum Decoded {
+  Packet(Inbound),
+  /// Understood, but nothing the handlers need.
+  Skip,
+  /// Left to `steven_protocol`.
+  Other,
+}
+
+/// Decodes the chat packets of 1.19 and later, `body` starts after the id.
+pub fn decode(
+  protocol_version: i32,
+  id: i32,
+  body: &mut io::Cursor<Vec<u8>>,
+) -> Result<Decoded, protocol::Error> {
+  let ids = match ids(protocol_version) {
+    Some(ids) => ids,
+    None => return Ok(Decoded::Other),
+  };
+  let inbound = if id == ids.player_chat {
+    player_chat(protocol_version, body)?
+  } else if id == ids.system_chat {
+    let content = read_component(body)?;
+    let position = if protocol_version == 759 {
+      // the vanilla chat types, 2 is the action bar
+      match VarInt::read_from(body)?.0 {
+        2 => 2,
+        _ => 1,
+      }
+    } else if bool::read_from(body)? {
+      2
+    } else {
+      1
+    };
+    inbound(content, position, nil_uuid())
+  } else if Some(id) == ids.disguised_chat {
+    let content = read_component(body)?;
+    let chat_type = VarInt::read_from(body)?.0;
+    let name = read_component(body)?;
+    let target = read_optional(body, read_component)?;
+    let (chat, position) = decorate(protocol_version, chat_type, name, target, content);
+    inbound(chat, position, nil_uuid())
+  } else if id == ids.server_data {
+    if enforces_secure_chat(protocol_version, body)? {
+      warn!(
+        target: TARGET,
+        "The server enforces secure chat, which the bot does not sign. It may be kicked as \
+         soon as it speaks, unless `enforce-secure-profile=false` is set in server.properties"
+      );
+    }
+    return Ok(Decoded::Skip);
+  } else {
+    return Ok(Decoded::Other);
+  };
+  Ok(Decoded::Packet(inbound))
+}
+
+fn player_chat(
+  protocol_version: i32,
+  r: &mut io::Cursor<Vec<u8>>,
+) -> Result<Inbound, protocol::Error> {
+  // timestamps, salts and signatures are of no use without verifying them
+  let (sender, content) = match protocol_version {
+    759 => {
+      let signed = read_component(r)?;
+      let unsigned = read_optional(r, read_component)?;
+      let chat_type = VarInt::read_from(r)?.0;
+      let sender = UUID::read_from(r)?;
+      let name = read_component(r)?;
+      let target = read_optional(r, read_component)?;
+      let content = unsigned.unwrap_or(signed);
+      return Ok(finish(protocol_version, chat_type, sender, name, target, content));
+    }
+    760 => {
+      read_optional(r, skip_byte_array)?;
+      let sender = UUID::read_from(r)?;
+      skip_byte_array(r)?;
+      let plain = String::read_from(r)?;
+      let formatted = read_optional(r, read_component)?;
+      skip(r, 16)?;
+      for _ in 0..VarInt::read_from(r)?.0 {
+        UUID::read_from(r)?;
+        skip_byte_array(r)?;
+      }
+      let unsigned = read_optional(r, read_component)?;
+      let content = unsigned
+        .or(formatted)
+        .unwrap_or_else(|| Component::text(plain));
+      (sender, content)
+    }
+    _ => {
+      let sender = UUID::read_from(r)?;
+      VarInt::read_from(r)?;
+      read_optional(r, |r| skip(r, 256))?;
+      let plain = String::read_from(r)?;
+      skip(r, 16)?;
+      for _ in 0..VarInt::read_from(r)?.0 {
+        // only new signatures are sent in full, known ones by id + 1
+        if VarInt::read_from(r)?.0 == 0 {
+          skip(r, 256)?;
+        }
+      }
+      let unsigned = read_optional(r, read_component)?;
+      (sender, unsigned.unwrap_or_else(|| Component::text(plain)))
+    }
+  };
+  // the filter mask, a long array when partially filtered
+  if VarInt::read_from(r)?.0 == 2 {
+    let longs = VarInt::read_from(r)?.0;
+    skip(r, longs as usize * 8)?;
+  }
+  let chat_type = VarInt::read_from(r)?.0;
+  let name = read_component(r)?;
+  let target = read_optional(r, read_component)?;
+  Ok(finish(protocol_version, chat_type, sender, name, target, content))
+}
+
+fn finish(
+  protocol_version: i32,
+  chat_type: i32,
+  sender: UUID,
+  name: Component,
+  target: Option<Component>,
+  content: Component,
+) -> Inbound {
+  let (chat, position) = decorate(protocol_version, chat_type, name, target, content);
+  inbound(chat, position, sender)
+}
+
+// Builds what the vanilla chat types of the `chat_type` registry show, servers
+// with data packs changing it get the vanilla look.
+fn decorate(
+  protocol_version: i32,
+  chat_type: i32,
+  name: Component,
+  target: Option<Component>,
+  content: Component,
+) -> (Component, u8) {
+  let key = match (protocol_version, chat_type) {
+    (759, 0) => "chat.type.text",
+    (759, 1) => return (content, 1),
+    (759, 2) => return (content, 2),
+    (759, 3) => "chat.type.announcement",
+    (759, 4) => "commands.message.display.incoming",
+    (759, 5) => "chat.type.team.text",
+    (759, 6) => "chat.type.emote",
+    (759, _) => return (content, 1),
+    (_, 0) => "chat.type.text",
+    (_, 1) => "chat.type.announcement",
+    (_, 2) => "commands.message.display.incoming",
+    (_, 3) => "commands.message.display.outgoing",
+    (_, 4) => "chat.type.team.text",
+    (_, 5) => "chat.type.team.sent",
+    (_, 6) => "chat.type.emote",
+    _ => "chat.type.text",
+  };
+  let with = match key {
+    "chat.type.team.text" | "chat.type.team.sent" => {
+      vec![target.unwrap_or_default(), name, content]
+    }
+    "commands.message.display.outgoing" => vec![target.unwrap_or_default(), content],
+    _ => vec![name, content],
+  };
+  let chat = Component {
+    content: Content::Translate {
+      key: key.to_owned(),
+      with,
+    },
+    ..Default::default()
+  };
+  (chat, 0)
+}
+
+fn inbound(chat: Component, position: u8, sender: UUID) -> Inbound {
+  let text = chat.to_plain(Lang::current());
+  let packet = Packet::ServerMessage_Sender(ServerMessage_Sender {
+    message: format::Component::Text(format::TextComponent::new(&text)),
+    position,
+    sender,
+  });
+  Inbound {
+    packet,
+    chat: Some(chat),
+  }
+}
+
+fn enforces_secure_chat(
+  protocol_version: i32,
+  r: &mut io::Cursor<Vec<u8>>,
+) -> Result<bool, protocol::Error> {
+  match protocol_version {
+    // not announced, 1.19 servers enforce it at login
+    759 => return Ok(false),
+    760 | 761 => {
+      read_optional(r, read_component)?;
+      read_optional(r, |r| String::read_from(r).map(drop))?;
+    }
+    _ => {
+      read_component(r)?;
+      read_optional(r, skip_byte_array)?;
+    }
+  }
+  if protocol_version == 760 {
+    // previews chat
+    bool::read_from(r)?;
+  }
+  bool::read_from(r)
+}
+
+fn nil_uuid() -> UUID {
+  UUID::read_from(&mut &[0u8; 16][..]).unwrap()
+}
+
+pub(super) fn read_component(r: &mut io::Cursor<Vec<u8>>) -> Result<Component, protocol::Error> {
+  let json = String::read_from(r)?;
+  Ok(match serde_json::from_str(&json) {
+    Ok(value) => Component::from_json(&value),
+    Err(_) => Component::text(json),
+  })
+}
+
+pub(super) fn read_optional<T>(
+  r: &mut io::Cursor<Vec<u8>>,
+  read: impl FnOnce(&mut io::Cursor<Vec<u8>>) -> Result<T, protocol::Error>,
+) -> Result<Option<T>, protocol::Error> {
+  if bool::read_from(r)? {
+    read(r).map(Some)
+  } else {
+    Ok(None)
+  }
+}
+
+pub(super) fn skip(r: &mut io::Cursor<Vec<u8>>, len: usize) -> Result<(), protocol::Error> {
+  let skipped = io::copy(&mut r.by_ref().take(len as u64), &mut io::sink())?;
+  if skipped < len as u64 {
+    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+  }
+  Ok(())
+}
+
+pub(super) fn skip_byte_array(r: &mut io::Cursor<Vec<u8>>) -> Result<(), protocol::Error> {
+  let len = VarInt::read_from(r)?.0;
+  skip(r, len as usize)
+}
+
+/// The id of the serverbound chat or command packet, for the versions whose
+/// chat is handled here.
+pub fn chat_packet_id(protocol_version: i32, command: bool) -> Option<i32> {
+  ids(protocol_version).map(|ids| {
+    if command {
+      ids.chat_command
+    } else {
+      ids.chat_message
+    }
+  })
+}
+
+/// Writes an unsigned chat message, or a command without its leading `/`, in
+/// the layout of `protocol_version`.
+pub fn write_chat<W: Write>(
+  protocol_version: i32,
+  text: &str,
+  buf: &mut W,
+) -> Result<(), protocol::Error> {
+  let (command, text) = match text.strip_prefix('/') {
+    Some(command) => (true, command),
+    None => (false, text),
+  };
+  text.to_owned().write_to(buf)?;
+  let timestamp = chrono::Utc::now().timestamp_millis();
+  timestamp.write_to(buf)?;
+  rand::random::<i64>().write_to(buf)?;
+  match protocol_version {
+    759 | 760 => {
+      // no argument signatures for a command, an empty signature otherwise
+      VarInt(0).write_to(buf)?;
+      // signed preview
+      false.write_to(buf)?;
+      if protocol_version == 760 {
+        // no last seen messages, none last received
+        VarInt(0).write_to(buf)?;
+        false.write_to(buf)?;
+      }
+    }
+    _ => {
+      if command {
+        // no argument signatures
+        VarInt(0).write_to(buf)?;
+      } else {
+        // no signature
+        false.write_to(buf)?;
+      }
+      // no acknowledged messages, an empty bitset of 20 bits
+      VarInt(0).write_to(buf)?;
+      buf.write_all(&[0; 3])?;
+    }
+  }
+  Ok(())
+}
+
+// the id of `LoginStart` never changed
+pub const LOGIN_START_ID: i32 = 0x00;
+
+/// Whether the `LoginStart` of `protocol_version` is written here rather than
+/// by `steven_protocol`.
+pub fn has_own_login_start(protocol_version: i32) -> bool {
+  ids(protocol_version).is_some()
+}
+
+/// Writes the `LoginStart` of 1.19 up to 1.20.1. The bot has no chat session,
+/// so the signature data is always left out, and the uuid is sent where the
+/// server takes it.
+pub fn write_login_start<W: Write>(
+  protocol_version: i32,
+  username: &str,
+  uuid: Option<uuid::Uuid>,
+  buf: &mut W,
+) -> Result<(), protocol::Error> {
+  username.to_owned().write_to(buf)?;
+  // the signature data was dropped in 1.19.3
+  if protocol_version <= 760 {
+    false.write_to(buf)?;
+  }
+  // the uuid was added in 1.19.1
+  if protocol_version >= 760 {
+    match uuid {
+      Some(uuid) => {
+        true.write_to(buf)?;
+        buf.write_all(uuid.as_bytes())?;
+      }
+      None => false.write_to(buf)?,
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn login_start(protocol_version: i32, uuid: Option<uuid::Uuid>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_login_start(protocol_version, "Bot", uuid, &mut buf).unwrap();
+    buf
+  }
+
+  #[test]
+  fn writes_login_start_of_each_version() {
+    let uuid = uuid::Uuid::from_u128(0x0123456789abcdef0123456789abcdef);
+    let name = [3, b'B', b'o', b't'];
+    // 1.19: no signature data
+    assert_eq!(login_start(759, Some(uuid)), [&name[..], &[0]].concat());
+    // 1.19.1: no signature data, then the uuid
+    assert_eq!(
+      login_start(760, Some(uuid)),
+      [&name[..], &[0, 1], uuid.as_bytes()].concat()
+    );
+    // 1.19.3 and later: only the uuid
+    assert_eq!(login_start(763, Some(uuid)), [&name[..], &[1], uuid.as_bytes()].concat());
+    assert_eq!(login_start(763, None), [&name[..], &[0]].concat());
+    assert!(has_own_login_start(759) && has_own_login_start(763));
+    assert!(!has_own_login_start(758));
+  }
+}
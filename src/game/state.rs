@@ -0,0 +1,195 @@
+use std::{
+  fmt,
+  sync::{
+    atomic::{AtomicU8, Ordering},
+    Mutex,
+  },
+};
+
+use steven_protocol::protocol;
+
+/// Where a connection is in the login and spawn sequence, in the order the
+/// server drives it through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Step {
+  Handshake = 0,
+  Login,
+  Encryption,
+  Compression,
+  Play,
+  JoinGame,
+  ClientSettings,
+  Spawned,
+}
+
+impl Step {
+  const ALL: [Step; 8] = [
+    Step::Handshake,
+    Step::Login,
+    Step::Encryption,
+    Step::Compression,
+    Step::Play,
+    Step::JoinGame,
+    Step::ClientSettings,
+    Step::Spawned,
+  ];
+
+  // offline servers skip encryption, and compression is up to the server
+  fn is_optional(self) -> bool {
+    matches!(self, Step::Encryption | Step::Compression)
+  }
+
+  fn from_u8(v: u8) -> Step {
+    Self::ALL[v as usize]
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepError {
+  pub current: Step,
+  pub requested: Step,
+}
+
+impl fmt::Display for StepError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "out of order packet: cannot enter {:?} while in {:?}",
+      self.requested, self.current
+    )
+  }
+}
+
+impl std::error::Error for StepError {}
+
+impl From<StepError> for protocol::Error {
+  fn from(err: StepError) -> Self {
+    protocol::Error::Err(err.to_string())
+  }
+}
+
+/// Tracks the [Step] of one connection, shared between the login code and
+/// the packet handlers.
+#[derive(Debug)]
+pub struct ConnState {
+  step: AtomicU8,
+  // the entity of the bot, known from JoinGame on
+  entity_id: Mutex<Option<i32>>,
+}
+
+impl Default for ConnState {
+  fn default() -> Self {
+    Self {
+      step: AtomicU8::new(Step::Handshake as u8),
+      entity_id: Mutex::new(None),
+    }
+  }
+}
+
+impl ConnState {
+  pub fn entity_id(&self) -> Option<i32> {
+    *self.entity_id.lock().unwrap()
+  }
+
+  pub fn set_entity_id(&self, entity_id: i32) {
+    *self.entity_id.lock().unwrap() = Some(entity_id);
+  }
+
+  pub fn current(&self) -> Step {
+    Step::from_u8(self.step.load(Ordering::Acquire))
+  }
+
+  pub fn is_at_least(&self, step: Step) -> bool {
+    self.current() >= step
+  }
+
+  /// Moves forward to `requested`. Optional steps may be skipped, anything
+  /// else going backwards or skipping a mandatory step is an error. Entering
+  /// [Step::Spawned] again is allowed, the server may respawn the bot, and so
+  /// is encryption after compression, which servers and proxies enable in
+  /// either order.
+  pub fn advance(&self, requested: Step) -> Result<(), StepError> {
+    self
+      .step
+      .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+        let current = Step::from_u8(current);
+        let allowed = if current == Step::Spawned {
+          requested == Step::Spawned
+        } else if current == Step::Compression && requested == Step::Encryption {
+          true
+        } else {
+          requested > current
+            && Step::ALL[current as usize + 1..requested as usize]
+              .iter()
+              .all(|step| step.is_optional())
+        };
+        allowed.then(|| requested as u8)
+      })
+      .map(|_| ())
+      .map_err(|current| StepError {
+        current: Step::from_u8(current),
+        requested,
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn walk(steps: &[Step]) -> ConnState {
+    let state = ConnState::default();
+    for step in steps {
+      state.advance(*step).unwrap();
+    }
+    state
+  }
+
+  const SPAWN: [Step; 4] = [Step::Play, Step::JoinGame, Step::ClientSettings, Step::Spawned];
+
+  #[test]
+  fn follows_the_offline_path() {
+    let state = walk(&[Step::Login]);
+    for step in SPAWN {
+      state.advance(step).unwrap();
+    }
+    assert_eq!(state.current(), Step::Spawned);
+    // respawning
+    state.advance(Step::Spawned).unwrap();
+  }
+
+  #[test]
+  fn follows_the_online_path() {
+    walk(&[Step::Login, Step::Encryption, Step::Compression, Step::Play]);
+    // compression first, as some proxies do
+    let state = walk(&[Step::Login, Step::Compression, Step::Encryption, Step::Play]);
+    assert!(state.is_at_least(Step::Play));
+    assert!(!state.is_at_least(Step::JoinGame));
+  }
+
+  #[test]
+  fn refuses_out_of_order_packets() {
+    let state = walk(&[Step::Login, Step::Play, Step::JoinGame]);
+    let err = state.advance(Step::JoinGame).unwrap_err();
+    assert_eq!(
+      err,
+      StepError {
+        current: Step::JoinGame,
+        requested: Step::JoinGame,
+      }
+    );
+    let state = walk(&[Step::Login]);
+    for step in SPAWN {
+      state.advance(step).unwrap();
+    }
+    assert!(state.advance(Step::JoinGame).is_err());
+    let state = walk(&[Step::Login, Step::Play]);
+    assert!(state.advance(Step::Encryption).is_err());
+
+    let err = ConnState::default().advance(Step::Play).unwrap_err();
+    assert_eq!(err.to_string(), "out of order packet: cannot enter Play while in Handshake");
+    let err = walk(&[Step::Login]).advance(Step::JoinGame).unwrap_err();
+    assert_eq!(err.to_string(), "out of order packet: cannot enter JoinGame while in Login");
+  }
+}
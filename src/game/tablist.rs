@@ -0,0 +1,244 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use steven_protocol::protocol::packet::{Packet, PlayerDetail, PlayerInfoData};
+use uuid::Uuid;
+
+use crate::exts::{component::ComponentExt, uuid::UuidExt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+  Survival,
+  Creative,
+  Adventure,
+  Spectator,
+}
+
+impl GameMode {
+  fn from_id(id: i32) -> Option<Self> {
+    match id {
+      0 => Some(GameMode::Survival),
+      1 => Some(GameMode::Creative),
+      2 => Some(GameMode::Adventure),
+      3 => Some(GameMode::Spectator),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for GameMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      GameMode::Survival => "survival",
+      GameMode::Creative => "creative",
+      GameMode::Adventure => "adventure",
+      GameMode::Spectator => "spectator",
+    };
+    f.write_str(name)
+  }
+}
+
+/// A player in the tab list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+  // before 1.8 the tab list only has names
+  pub uuid: Option<Uuid>,
+  pub name: String,
+  // set by plugins, with ranks or nicknames
+  pub display_name: Option<String>,
+  pub game_mode: Option<GameMode>,
+  // in milliseconds, as measured by the server
+  pub latency: i32,
+}
+
+impl Player {
+  /// The name as shown in the tab list.
+  pub fn shown_name(&self) -> &str {
+    self.display_name.as_deref().unwrap_or(&self.name)
+  }
+
+  // NPC plugins such as Citizens list their NPCs with version 2 uuids
+  fn is_npc(&self) -> bool {
+    self.uuid.map_or(false, |uuid| uuid.get_version_num() == 2)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+  Uuid(Uuid),
+  Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TabEvent {
+  Joined(Player),
+  Left(Player),
+}
+
+/// The players online on the server, as the tab list shows them.
+#[derive(Debug)]
+pub struct TabList {
+  players: Mutex<HashMap<Key, Player>>,
+  bot_uuid: Option<Uuid>,
+  bot_name: String,
+  // when the bot joined the game, after which the server sends everyone online
+  joined: Mutex<Option<Instant>>,
+}
+
+impl TabList {
+  pub fn new(bot_uuid: Option<Uuid>, bot_name: String) -> Self {
+    Self {
+      players: Default::default(),
+      bot_uuid,
+      bot_name,
+      joined: Mutex::new(None),
+    }
+  }
+
+  /// Marks the bot as having joined the game.
+  pub fn start(&self) {
+    *self.joined.lock().unwrap() = Some(Instant::now());
+  }
+
+  /// Whether the bot joined less than `period` ago, or not yet, while the
+  /// server sends everyone already online.
+  pub fn is_filling(&self, period: Duration) -> bool {
+    match *self.joined.lock().unwrap() {
+      Some(joined) => joined.elapsed() < period,
+      None => true,
+    }
+  }
+
+  /// Everyone online but the bot and NPCs, sorted by name.
+  pub fn players(&self) -> Vec<Player> {
+    let mut players: Vec<Player> = self
+      .players
+      .lock()
+      .unwrap()
+      .values()
+      .filter(|player| !self.is_bot(player) && !player.is_npc())
+      .cloned()
+      .collect();
+    players.sort_by_key(|player| player.name.to_lowercase());
+    players
+  }
+
+  /// Applies a `PlayerInfo` packet, returning who joined and who left. Other
+  /// packets are ignored.
+  pub fn apply(&self, packet: &Packet) -> Vec<TabEvent> {
+    let mut players = self.players.lock().unwrap();
+    let mut events = Vec::new();
+    match packet {
+      Packet::PlayerInfo(info) => {
+        let details = match &info.inner {
+          PlayerInfoData::Add(details)
+          | PlayerInfoData::UpdateGamemode(details)
+          | PlayerInfoData::UpdateLatency(details)
+          | PlayerInfoData::UpdateDisplayName(details)
+          | PlayerInfoData::Remove(details) => &details.data,
+        };
+        for detail in details {
+          self.apply_detail(&mut players, detail, &mut events);
+        }
+      }
+      // 1.7 sends one name at a time
+      Packet::PlayerInfo_String(info) => {
+        let key = Key::Name(info.name.clone());
+        if info.online {
+          let player = Player {
+            uuid: None,
+            name: info.name.clone(),
+            display_name: None,
+            game_mode: None,
+            latency: info.ping as i32,
+          };
+          if players.insert(key, player.clone()).is_none() {
+            events.push(TabEvent::Joined(player));
+          }
+        } else if let Some(player) = players.remove(&key) {
+          events.push(TabEvent::Left(player));
+        }
+      }
+      _ => {}
+    }
+    events.retain(|event| match event {
+      TabEvent::Joined(player) | TabEvent::Left(player) => {
+        !self.is_bot(player) && !player.is_npc()
+      }
+    });
+    events
+  }
+
+  fn apply_detail(
+    &self,
+    players: &mut HashMap<Key, Player>,
+    detail: &PlayerDetail,
+    events: &mut Vec<TabEvent>,
+  ) {
+    let uuid = match detail {
+      PlayerDetail::Add { uuid, .. }
+      | PlayerDetail::UpdateGamemode { uuid, .. }
+      | PlayerDetail::UpdateLatency { uuid, .. }
+      | PlayerDetail::UpdateDisplayName { uuid, .. }
+      | PlayerDetail::Remove { uuid } => uuid,
+    };
+    // nobody has the nil uuid
+    let uuid = match uuid.to_uuid() {
+      Some(uuid) => uuid,
+      None => return,
+    };
+    let key = Key::Uuid(uuid);
+    match detail {
+      PlayerDetail::Add {
+        name,
+        gamemode,
+        ping,
+        display,
+        ..
+      } => {
+        let player = Player {
+          uuid: Some(uuid),
+          name: name.clone(),
+          display_name: display.as_ref().map(|display| display.to_plain()),
+          game_mode: GameMode::from_id(gamemode.0),
+          latency: ping.0,
+        };
+        // an add for someone already listed only refreshes the entry
+        if players.insert(key, player.clone()).is_none() {
+          events.push(TabEvent::Joined(player));
+        }
+      }
+      PlayerDetail::UpdateGamemode { gamemode, .. } => {
+        if let Some(player) = players.get_mut(&key) {
+          player.game_mode = GameMode::from_id(gamemode.0);
+        }
+      }
+      PlayerDetail::UpdateLatency { ping, .. } => {
+        if let Some(player) = players.get_mut(&key) {
+          player.latency = ping.0;
+        }
+      }
+      PlayerDetail::UpdateDisplayName { display, .. } => {
+        if let Some(player) = players.get_mut(&key) {
+          player.display_name = display.as_ref().map(|display| display.to_plain());
+        }
+      }
+      PlayerDetail::Remove { .. } => {
+        if let Some(player) = players.remove(&key) {
+          events.push(TabEvent::Left(player));
+        }
+      }
+    }
+  }
+
+  fn is_bot(&self, player: &Player) -> bool {
+    match (player.uuid, self.bot_uuid) {
+      (Some(uuid), Some(bot)) => uuid == bot,
+      _ => player.name.eq_ignore_ascii_case(&self.bot_name),
+    }
+  }
+}
@@ -0,0 +1,265 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use color_eyre::eyre;
+use tokio::{sync::Notify, time::Instant};
+use tracing::{debug, warn};
+
+use super::{outbound::Outbound, queue::QueueSender, TARGET};
+use crate::{
+  chat::{
+    echo::EchoGuard,
+    sanitize,
+    tellraw::{Rich, Tellraw},
+  },
+  config::{Overflow, ThrottleConfig},
+};
+
+/// Something to say in game.
+#[derive(Debug)]
+pub enum Outgoing {
+  Line(String),
+  /// Sent as its chat lines once `tellraw` is turned off.
+  Rich(Rich),
+}
+
+/// Paces the chat lines and commands the bot sends, so anti-spam measures of
+/// the server do not kick it. Every other packet goes to the write queue
+/// directly and is never held back.
+#[derive(Debug)]
+pub struct ChatThrottle {
+  config: &'static ThrottleConfig,
+  limit: usize,
+  pending: Mutex<Pending>,
+  // wakes the sending task when a line is queued
+  queued: Notify,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+  lines: VecDeque<Outgoing>,
+  // lines which did not fit into the queue since the last summary
+  overflowed: usize,
+  dropped: u64,
+}
+
+impl ChatThrottle {
+  pub fn new(config: &'static ThrottleConfig, protocol_version: i32) -> Self {
+    Self {
+      config,
+      limit: sanitize::chat_limit(protocol_version),
+      pending: Default::default(),
+      queued: Notify::new(),
+    }
+  }
+
+  /// Queues a line, applying the overflow policy when the queue is full. Never
+  /// waits, so the bridge keeps up with remote messages.
+  pub fn send(&self, line: Outgoing) {
+    let mut pending = self.pending.lock().unwrap();
+    pending.lines.push_back(line);
+    self.trim(&mut pending, 0);
+    self.queued.notify_one();
+  }
+
+  /// Queues lines ahead of everything else, for lines replacing ones which
+  /// were already sent or a rich message which can no longer be sent. The
+  /// overflow policy applies to what does not fit behind them.
+  pub fn resend(&self, lines: Vec<String>) {
+    let mut pending = self.pending.lock().unwrap();
+    let ahead = lines.len();
+    for line in lines.into_iter().rev() {
+      pending.lines.push_front(Outgoing::Line(line));
+    }
+    self.trim(&mut pending, ahead);
+    self.queued.notify_one();
+  }
+
+  // brings the queue back to its limit, `ahead` lines at the front having just
+  // been put there
+  fn trim(&self, pending: &mut Pending, ahead: usize) {
+    let excess = pending.lines.len().saturating_sub(self.config.pending);
+    if excess == 0 {
+      return;
+    }
+    match self.config.overflow {
+      Overflow::DropOldest => {
+        // the oldest lines are the ones behind those put ahead
+        let start = ahead.min(self.config.pending);
+        pending.lines.drain(start..start + excess);
+        let before = pending.dropped;
+        pending.dropped += excess as u64;
+        // warns at every power of two
+        if before.leading_zeros() != pending.dropped.leading_zeros() {
+          warn!(
+            target: TARGET,
+            "Chat is sent slower than it arrives, {} lines dropped so far", pending.dropped
+          );
+        }
+      }
+      Overflow::Merge => {
+        pending.lines.truncate(self.config.pending);
+        pending.overflowed += excess;
+      }
+      Overflow::Delay => {}
+    }
+  }
+
+  /// Sends the queued lines as fast as the rate allows, until the write queue
+  /// is closed.
+  pub async fn run(
+    self: Arc<Self>,
+    write_tx: QueueSender<Outbound>,
+    echo: Arc<EchoGuard>,
+    tellraw: Arc<Tellraw>,
+  ) -> eyre::Result<()> {
+    let mut bucket = TokenBucket::new(self.config.rate, self.config.burst);
+    loop {
+      let outgoing = loop {
+        if let Some(outgoing) = self.next_line() {
+          break outgoing;
+        }
+        self.queued.notified().await;
+      };
+      let outgoing = match outgoing {
+        // queued before the server refused an earlier one
+        Outgoing::Rich(rich) if !tellraw.is_enabled() => {
+          self.resend(rich.fallback);
+          continue;
+        }
+        outgoing => outgoing,
+      };
+      bucket.take().await;
+      // recorded when sent, the line may have waited longer than the echo window
+      let line = match outgoing {
+        Outgoing::Line(line) => line,
+        Outgoing::Rich(rich) => {
+          echo.record(&rich.text);
+          tellraw.sent(rich.fallback);
+          rich.command
+        }
+      };
+      echo.record(&line);
+      write_tx.send(Outbound::chat(line)).await?;
+    }
+  }
+
+  fn next_line(&self) -> Option<Outgoing> {
+    let mut pending = self.pending.lock().unwrap();
+    if let Some(line) = pending.lines.pop_front() {
+      return Some(line);
+    }
+    if pending.overflowed == 0 {
+      return None;
+    }
+    let count = std::mem::take(&mut pending.overflowed);
+    debug!(target: TARGET, "Merged {} lines into a summary", count);
+    let summary = self.config.summary.replace("{count}", &count.to_string());
+    sanitize::split(&sanitize::sanitize(&summary), self.limit, 1)
+      .pop()
+      .map(Outgoing::Line)
+  }
+}
+
+struct TokenBucket {
+  // tokens per second
+  rate: f64,
+  burst: f64,
+  tokens: f64,
+  last: Instant,
+}
+
+impl TokenBucket {
+  fn new(rate: f64, burst: u32) -> Self {
+    Self {
+      rate,
+      burst: burst as f64,
+      tokens: burst as f64,
+      last: Instant::now(),
+    }
+  }
+
+  fn refill(&mut self) {
+    let now = Instant::now();
+    let elapsed = now.duration_since(self.last).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+    self.last = now;
+  }
+
+  async fn take(&mut self) {
+    self.refill();
+    if self.tokens < 1.0 {
+      let wait = (1.0 - self.tokens) / self.rate;
+      tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+      self.refill();
+    }
+    self.tokens -= 1.0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn throttle(overflow: Overflow) -> ChatThrottle {
+    let config = ThrottleConfig {
+      pending: 3,
+      overflow,
+      ..Default::default()
+    };
+    ChatThrottle::new(Box::leak(Box::new(config)), 340)
+  }
+
+  fn line(text: &str) -> Outgoing {
+    Outgoing::Line(text.to_owned())
+  }
+
+  fn lines(throttle: &ChatThrottle) -> Vec<String> {
+    std::iter::from_fn(|| throttle.next_line())
+      .map(|outgoing| match outgoing {
+        Outgoing::Line(line) => line,
+        Outgoing::Rich(rich) => rich.command,
+      })
+      .collect()
+  }
+
+  fn fallback(texts: &[&str]) -> Vec<String> {
+    texts.iter().map(|text| text.to_string()).collect()
+  }
+
+  #[test]
+  fn merges_what_does_not_fit() {
+    let throttle = throttle(Overflow::Merge);
+    for text in ["a", "b", "c", "d", "e"] {
+      throttle.send(line(text));
+    }
+    throttle.resend(fallback(&["x", "y"]));
+    assert_eq!(
+      lines(&throttle),
+      ["x", "y", "a", "[Mesagisto] 4 more messages were not shown"]
+    );
+  }
+
+  #[test]
+  fn drops_the_oldest_behind_resent_lines() {
+    let throttle = throttle(Overflow::DropOldest);
+    for text in ["a", "b", "c", "d"] {
+      throttle.send(line(text));
+    }
+    throttle.resend(fallback(&["x", "y"]));
+    assert_eq!(lines(&throttle), ["x", "y", "d"]);
+  }
+
+  #[test]
+  fn delays_without_dropping() {
+    let throttle = throttle(Overflow::Delay);
+    for text in ["a", "b", "c", "d"] {
+      throttle.send(line(text));
+    }
+    throttle.resend(fallback(&["x"]));
+    assert_eq!(lines(&throttle), ["x", "a", "b", "c", "d"]);
+  }
+}
@@ -1,12 +1,16 @@
 use std::sync::Arc;
 
-use steven_protocol::protocol::packet::{self, Packet};
-use tokio::sync::mpsc::UnboundedSender;
-use tracing::{debug, trace};
+use steven_protocol::protocol::packet::Packet;
+use tracing::{trace, warn};
 
-use crate::game::Client;
-
-use super::PacketHandler;
+use super::{PacketHandler, TARGET};
+use crate::{
+  bridge::Bridge,
+  chat::{echo::EchoGuard, kind::ChatKind, pattern::Patterns, Component, Lang},
+  config::{Route, CONFIG},
+  data::{ChatMessage, Sender},
+  exts::{component::ComponentExt, uuid::UuidExt},
+};
 
 pub fn chat_handler() -> PacketHandler {
   dptree::filter(|pkt: Arc<Packet>| {
@@ -15,47 +19,166 @@ pub fn chat_handler() -> PacketHandler {
       Packet::ServerMessage_Sender(_) => pass = true,
       Packet::ServerMessage_Position(_) => pass = true,
       Packet::ServerMessage_NoPosition(_) => pass = true,
+      Packet::ActionBar(_) => pass = true,
       _ => {}
     }
     pass
   })
   .endpoint(
-    |pkt: Arc<Packet>, write_tx: UnboundedSender<Packet>, client: Arc<Client>,| async move {
-      match pkt.as_ref().to_owned() {
+    |pkt: Arc<Packet>,
+     chat: Option<Arc<Component>>,
+     bridge: Arc<dyn Bridge>,
+     echo: Arc<EchoGuard>| async move {
+      let (kind, message, uuid) = match pkt.as_ref() {
+        Packet::ServerMessage_Sender(v) => (
+          ChatKind::from_position(v.position),
+          &v.message,
+          v.sender.to_uuid(),
+        ),
         Packet::ServerMessage_Position(v) => {
-          if v.position != 0 {
-            trace!("Not a message from player{:#?}", v);
-          }
-          let msg = match v.message {
-            steven_protocol::format::Component::Text(v) => v,
+          (ChatKind::from_position(v.position), &v.message, None)
+        }
+        // before 1.8 everything went to the chat box
+        Packet::ServerMessage_NoPosition(v) => (ChatKind::Chat, &v.message, None),
+        Packet::ActionBar(v) => (ChatKind::GameInfo, &v.text, None),
+        _ => return Ok(()),
+      };
+      let route = kind.route();
+      if route == Route::Drop {
+        return Ok(());
+      }
+
+      let lang = Lang::current();
+      let (plain, line) = match &chat {
+        Some(chat) => (chat.to_plain(lang), chat.to_chat_line(lang)),
+        // the component was not valid JSON, only its text is known
+        None => {
+          let plain = message.to_plain();
+          (plain.clone(), Component::text(plain).to_chat_line(lang))
+        }
+      };
+      // announced from the tab list instead, which sees players joining even
+      // when plugins hide these messages
+      if CONFIG.tablist.announce && line.key.as_deref().map_or(false, is_join_or_leave) {
+        return Ok(());
+      }
+      let said = match line.sender {
+        Some(name) => Some((
+          Sender {
+            name,
+            uuid,
+            prefix: None,
+          },
+          line.message,
+        )),
+        // plugins send chat as plain text in a format of their own
+        None => Patterns::current().parse(&line.message).map(|parsed| {
+          (
+            Sender {
+              name: parsed.sender,
+              uuid,
+              prefix: parsed.prefix,
+            },
+            parsed.message,
+          )
+        }),
+      };
+      let echoed = match &said {
+        Some((sender, content)) => echo.is_echo(sender, content),
+        None => echo.was_sent(&plain),
+      };
+      if echoed {
+        trace!(target: TARGET, "Not relaying what the bot said: {}", plain);
+        return Ok(());
+      }
+
+      trace!(target: TARGET, "{:?} line routed to {:?}: {}", kind, route, plain);
+      let result = match (route, said) {
+        (Route::Relay, Some((sender, content))) => {
+          let content = match &line.styled {
+            Some(styled) if CONFIG.mesagisto.markdown => styled.to_markdown(lang),
+            _ => content,
           };
-          let extra: Vec<String> = msg
-            .modifier
-            .extra
-            .unwrap_or_else(|| Vec::new())
-            .into_iter()
-            .filter_map(|v| {
-              let msg = match v {
-                steven_protocol::format::Component::Text(v) => v.text,
-              };
-              if !msg.is_empty() { Some(msg) } else { None }
-            })
-            .collect();
-          let mut all = if msg.text.is_empty() { Vec::new() } else { vec![msg.text] };
-          all.extend(extra);
-          if all.is_empty() || all.iter().any(|s| s.contains(&client.profile.username) || s.contains("unhandled: ") ) { return Ok(()) }
-          for msg in all {
-            write_tx.send(
-              packet::play::serverbound::ChatMessage {
-                message: msg,
-              }
-              .into(),
-            )?;
-          }
+          bridge.publish(ChatMessage::new(sender, content)).await
         }
-        _ => {}
+        _ if plain.trim().is_empty() => Ok(()),
+        _ => bridge.notify(plain).await,
+      };
+      // the game connection outlives a hiccup of the bridge
+      if let Err(err) = result {
+        warn!(target: TARGET, "Failed to publish chat message: {:?}", err);
       }
       Ok(())
     },
   )
 }
+
+fn is_join_or_leave(key: &str) -> bool {
+  matches!(
+    key,
+    "multiplayer.player.joined" | "multiplayer.player.joined.renamed" | "multiplayer.player.left"
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+
+  use dptree::prelude::*;
+  use serde_json::{json, Value};
+  use steven_protocol::{
+    format,
+    protocol::{packet::play::clientbound::ServerMessage_Sender, UUID},
+  };
+
+  use super::*;
+  use crate::bridge::memory::{MemoryBridge, MemoryChannel};
+
+  async fn receive(bridge: &Arc<dyn Bridge>, echo: &Arc<EchoGuard>, position: u8, chat: Value) {
+    let chat = Component::from_json(&chat);
+    let text = chat.to_plain(Lang::current());
+    let packet = Packet::ServerMessage_Sender(ServerMessage_Sender {
+      message: format::Component::Text(format::TextComponent::new(&text)),
+      position,
+      sender: UUID::from_str("8667ba71-b85a-4004-af54-457a9734eed7").unwrap(),
+    });
+    let deps = dptree::deps![
+      Arc::new(packet),
+      Some(Arc::new(chat)),
+      bridge.clone(),
+      echo.clone()
+    ];
+    let result = chat_handler().dispatch(deps).await;
+    assert!(matches!(result, ControlFlow::Break(Ok(()))));
+  }
+
+  #[tokio::test]
+  async fn publishes_chat_to_the_channel() {
+    let channel = MemoryChannel::new(arcstr::literal!("survival"));
+    let bridge: Arc<dyn Bridge> = Arc::new(MemoryBridge::new(arcstr::literal!("bot"), &channel));
+    let other = MemoryBridge::new(arcstr::literal!("other"), &channel);
+    let mut incoming = other.subscribe();
+    let echo = Arc::new(EchoGuard::new(None, String::from("Bot")));
+
+    let said = json!({
+      "translate": "chat.type.text",
+      "with": [{ "text": "Steve", "insertion": "Steve" }, "hello"],
+    });
+    receive(&bridge, &echo, 0, said).await;
+    let message = incoming.recv().await.unwrap();
+    assert_eq!(message.sender, "Steve");
+    assert_eq!(message.content, "hello");
+    assert_eq!(message.channel, "survival");
+
+    // what the bot said itself is not relayed back
+    let own = json!({ "translate": "chat.type.text", "with": ["Bot", "hi"] });
+    receive(&bridge, &echo, 0, own).await;
+    // system lines go out whole, as said by the bot
+    let death = json!({ "translate": "death.attack.player", "with": ["Steve", "Alex"] });
+    receive(&bridge, &echo, 1, death).await;
+    let message = incoming.recv().await.unwrap();
+    assert_eq!(message.sender, "bot");
+    assert_eq!(message.content, "Steve was slain by Alex");
+    assert!(incoming.try_recv().is_err());
+  }
+}
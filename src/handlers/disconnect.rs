@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use steven_protocol::protocol::packet::Packet;
+use tracing::warn;
+
+use super::PacketHandler;
+use crate::{chat::Component, game::disconnect::Kicked};
+
+const TARGET: &str = "mesagisto::disconnect";
+
+pub fn disconnect_handler() -> PacketHandler {
+  dptree::filter(|pkt: Arc<Packet>| matches!(pkt.as_ref(), Packet::Disconnect(_))).endpoint(
+    |pkt: Arc<Packet>, chat: Option<Arc<Component>>| async move {
+      if let Packet::Disconnect(v) = pkt.as_ref() {
+        let kicked = Kicked::new(chat.as_deref(), &v.reason);
+        warn!(target: TARGET, "{} ({:?})", kicked, kicked.kind);
+        return Err(kicked.into());
+      }
+      Ok(())
+    },
+  )
+}
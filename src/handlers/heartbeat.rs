@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use steven_protocol::protocol::packet::{self, Packet};
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::trace;
 
 use super::PacketHandler;
+use crate::game::{outbound::Outbound, queue::QueueSender};
 
 const TARGET: &str = "mesagisto::heartbeat";
 
@@ -29,19 +29,24 @@ pub(crate) fn heartbeat_handler() -> PacketHandler {
     pass
   })
   .endpoint(
-    |pkt: Arc<Packet>, write_tx: UnboundedSender<Packet>| async move {
+    |pkt: Arc<Packet>, write_tx: QueueSender<Outbound>| async move {
       match pkt.as_ref() {
         Packet::KeepAliveClientbound_i64(v) => {
-          write_tx.send(packet::play::serverbound::KeepAliveServerbound_i64 { id: v.id }.into())?;
+          write_tx
+            .send(packet::play::serverbound::KeepAliveServerbound_i64 { id: v.id }.into())
+            .await?;
           trace!(target: TARGET, "Heartbeat Response {:?}", v.id);
         }
         Packet::KeepAliveClientbound_VarInt(v) => {
           write_tx
-            .send(packet::play::serverbound::KeepAliveServerbound_VarInt { id: v.id }.into())?;
+            .send(packet::play::serverbound::KeepAliveServerbound_VarInt { id: v.id }.into())
+            .await?;
           trace!(target: TARGET, "Heartbeat Response {:?}", v.id);
         }
         Packet::KeepAliveClientbound_i32(v) => {
-          write_tx.send(packet::play::serverbound::KeepAliveServerbound_i32 { id: v.id }.into())?;
+          write_tx
+            .send(packet::play::serverbound::KeepAliveServerbound_i32 { id: v.id }.into())
+            .await?;
           trace!(target: TARGET, "Heartbeat Response {:?}", v.id);
         }
         _ => {}
@@ -1,6 +1,11 @@
 pub mod chat;
+mod disconnect;
 mod heartbeat;
+mod query;
+pub mod remote;
 pub mod steps;
+mod tablist;
+mod tellraw;
 pub mod write;
 
 use std::sync::Arc;
@@ -11,33 +16,77 @@ use steven_protocol::protocol::{
   self,
   packet::{self, Packet},
 };
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::Receiver;
 use tracing::trace;
 
-use crate::game::Client;
+use crate::{
+  bridge::Bridge,
+  chat::{echo::EchoGuard, tellraw::Tellraw},
+  game::{
+    codec::Inbound, outbound::Outbound, queue::QueueSender, state::ConnState,
+    tablist::TabList, throttle::ChatThrottle, Client,
+  },
+};
 
-use self::heartbeat::heartbeat_handler;
+use self::{
+  disconnect::disconnect_handler, heartbeat::heartbeat_handler, tablist::tablist_handler,
+  tellraw::{permission_handler, refusal_handler},
+};
 
 const TARGET: &str = "mesagisto::handlers";
 
 type PacketHandler = Endpoint<'static, DependencyMap, Result<()>>;
 
+/// The handles of one connection the handlers share.
+#[derive(Clone)]
+pub struct ConnContext {
+  pub state: Arc<ConnState>,
+  pub write_tx: QueueSender<Outbound>,
+  pub bridge: Arc<dyn Bridge>,
+  pub echo: Arc<EchoGuard>,
+  pub throttle: Arc<ChatThrottle>,
+  pub tellraw: Arc<Tellraw>,
+  pub tablist: Arc<TabList>,
+}
+
+impl ConnContext {
+  // handlers take the whole context, or only the handles they need
+  fn deps(&self, client: Arc<Client>, inbound: Inbound) -> DependencyMap {
+    let Inbound { packet, chat } = inbound;
+    dptree::deps![
+      Arc::new(packet),
+      chat.map(Arc::new),
+      client,
+      Arc::new(self.clone()),
+      self.write_tx.clone(),
+      self.state.clone(),
+      self.bridge.clone(),
+      self.echo.clone(),
+      self.throttle.clone(),
+      self.tellraw.clone(),
+      self.tablist.clone()
+    ]
+  }
+}
+
 pub async fn init(
-  client: Client,
-  mut read_rx: UnboundedReceiver<Result<Packet, protocol::Error>>,
-  write_tx: UnboundedSender<Packet>,
+  client: Arc<Client>,
+  mut read_rx: Receiver<Result<Inbound, protocol::Error>>,
+  ctx: ConnContext,
 ) -> Result<()> {
   let packet_handler = dptree::entry()
     .branch(heartbeat_handler())
+    .branch(disconnect_handler())
     .branch(steps::step_10())
+    .branch(tablist_handler())
+    .branch(permission_handler())
+    .branch(refusal_handler())
     .branch(chat::chat_handler())
     .branch(default_handler());
 
-  let client = Arc::new(client);
-  while let Some(packet) = read_rx.recv().await {
-    let packet = Arc::new(packet?);
+  while let Some(inbound) = read_rx.recv().await {
     let ctrl_flow = packet_handler
-      .dispatch(dptree::deps![packet, write_tx.clone(), client.clone()])
+      .dispatch(ctx.deps(client.clone(), inbound?))
       .await;
     match ctrl_flow {
       ControlFlow::Continue(_) => {
@@ -56,27 +105,31 @@ pub async fn init(
 
 fn default_handler() -> PacketHandler {
   dptree::endpoint(
-    |pkt: Arc<Packet>, write: UnboundedSender<Packet>| async move {
+    |pkt: Arc<Packet>, write: QueueSender<Outbound>, state: Arc<ConnState>| async move {
       match pkt.as_ref() {
         Packet::TeleportPlayer_WithDismount(v) => {
           trace!(target: TARGET, "step 31 C->S Accept Teleportation");
-          write.send(
-            packet::play::serverbound::TeleportConfirm {
-              teleport_id: v.teleport_id,
-            }
-            .into(),
-          )?;
-          steps::step_33(write).await?;
+          write
+            .send(
+              packet::play::serverbound::TeleportConfirm {
+                teleport_id: v.teleport_id,
+              }
+              .into(),
+            )
+            .await?;
+          steps::step_33(write, state).await?;
         }
         Packet::TeleportPlayer_WithConfirm(v) => {
           trace!(target: TARGET, "step 31 C->S Accept Teleportation");
-          write.send(
-            packet::play::serverbound::TeleportConfirm {
-              teleport_id: v.teleport_id,
-            }
-            .into(),
-          )?;
-          steps::step_33(write).await?;
+          write
+            .send(
+              packet::play::serverbound::TeleportConfirm {
+                teleport_id: v.teleport_id,
+              }
+              .into(),
+            )
+            .await?;
+          steps::step_33(write, state).await?;
         }
 
         _ => {}
@@ -0,0 +1,123 @@
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use tracing::{debug, warn};
+
+use crate::{
+  bridge::Bridge,
+  chat::Lang,
+  config::CONFIG,
+  data::RemoteMessage,
+  game::{
+    state::{ConnState, Step},
+    tablist::{Player, TabList},
+  },
+};
+
+const TARGET: &str = "mesagisto::query";
+
+// when each channel was last answered, kept across reconnects
+static ANSWERED: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Default::default);
+
+/// Answers `message` when it asks who is online, from the tab list and without
+/// sending anything into the game. Returns whether it was such a query.
+pub async fn answer(
+  message: &RemoteMessage,
+  state: &ConnState,
+  tablist: &TabList,
+  bridge: &dyn Bridge,
+) -> bool {
+  let config = &CONFIG.query;
+  let command = config.list_command.trim();
+  if command.is_empty() || !message.content.trim().eq_ignore_ascii_case(command) {
+    return false;
+  }
+  if !take_turn(&message.channel, Duration::from_secs(config.cooldown)) {
+    debug!(target: TARGET, "Not answering {} again so soon", message.channel);
+    return true;
+  }
+  let lang = Lang::current();
+  // the tab list is incomplete until the server has sent everyone online
+  let quiet_period = Duration::from_secs(CONFIG.tablist.quiet_period);
+  let text = if !state.is_at_least(Step::Spawned) || tablist.is_filling(quiet_period) {
+    lang.translate("mesagisto.list.joining", &[])
+  } else {
+    list(&tablist.players(), config.details, lang)
+  };
+  if let Err(err) = bridge.notify(text).await {
+    warn!(target: TARGET, "Failed to answer who is online: {:?}", err);
+  }
+  true
+}
+
+fn take_turn(channel: &str, cooldown: Duration) -> bool {
+  let mut answered = ANSWERED.lock().unwrap();
+  let now = Instant::now();
+  match answered.get(channel) {
+    Some(at) if now.duration_since(*at) < cooldown => false,
+    _ => {
+      answered.insert(channel.to_owned(), now);
+      true
+    }
+  }
+}
+
+fn list(players: &[Player], details: bool, lang: &Lang) -> String {
+  if players.is_empty() {
+    return lang.translate("mesagisto.list.nobody", &[]);
+  }
+  let names: Vec<String> = players
+    .iter()
+    .map(|player| {
+      let name = player.shown_name().to_owned();
+      let latency = player.latency.to_string();
+      match (details, player.game_mode) {
+        (false, _) => name,
+        (true, Some(game_mode)) => {
+          let game_mode = lang.translate(&format!("gameMode.{}", game_mode), &[]);
+          lang.translate("mesagisto.list.player.mode", &[name, game_mode, latency])
+        }
+        (true, None) => lang.translate("mesagisto.list.player", &[name, latency]),
+      }
+    })
+    .collect();
+  let names = names.join(", ");
+  match players.len() {
+    1 => lang.translate("mesagisto.list.one", &[names]),
+    n => lang.translate("mesagisto.list.many", &[n.to_string(), names]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::tablist::GameMode;
+
+  fn player(name: &str, game_mode: Option<GameMode>) -> Player {
+    Player {
+      uuid: None,
+      name: name.to_owned(),
+      display_name: None,
+      game_mode,
+      latency: 25,
+    }
+  }
+
+  #[test]
+  fn lists_players_in_the_language() {
+    let en = Lang::load("en_us").unwrap();
+    assert_eq!(list(&[], true, &en), "Nobody is online");
+    let players = [player("Alex", Some(GameMode::Creative)), player("Steve", None)];
+    assert_eq!(list(&players[..1], false, &en), "1 player online: Alex");
+    assert_eq!(
+      list(&players, true, &en),
+      "2 players online: Alex (Creative Mode, 25 ms), Steve (25 ms)"
+    );
+    let zh = Lang::load("zh_cn").unwrap();
+    assert_eq!(list(&players, false, &zh), "2 名玩家在线：Alex, Steve");
+  }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use super::query;
+use crate::{
+  bridge::Bridge,
+  chat::{
+    sanitize,
+    tellraw::{self, Rich, Tellraw},
+    Component, Lang,
+  },
+  config::CONFIG,
+  data::RemoteMessage,
+  game::{
+    state::{ConnState, Step},
+    tablist::TabList,
+    throttle::{ChatThrottle, Outgoing},
+  },
+};
+
+const TARGET: &str = "mesagisto::remote";
+
+/// Sends every message from the bridge into the game chat, for as long as the
+/// connection lives. Queries for the bridge itself are answered instead.
+pub async fn forward(
+  bridge: Arc<dyn Bridge>,
+  state: Arc<ConnState>,
+  protocol_version: i32,
+  throttle: Arc<ChatThrottle>,
+  tellraw: Arc<Tellraw>,
+  tablist: Arc<TabList>,
+) -> eyre::Result<()> {
+  let limit = sanitize::chat_limit(protocol_version);
+  let mut incoming = bridge.subscribe();
+  loop {
+    let message = match incoming.recv().await {
+      Ok(message) => message,
+      Err(RecvError::Lagged(skipped)) => {
+        warn!(target: TARGET, "Skipped {} remote messages, the game is too slow", skipped);
+        continue;
+      }
+      Err(RecvError::Closed) => return Ok(()),
+    };
+    if query::answer(&message, &state, &tablist, bridge.as_ref()).await {
+      continue;
+    }
+    // the server ignores chat before the bot has spawned
+    if !state.is_at_least(Step::Spawned) {
+      debug!(target: TARGET, "Not spawned yet, dropping {:?}", message);
+      continue;
+    }
+    let rich = if tellraw.is_enabled() {
+      tellraw::build(&message, limit, Lang::current())
+    } else {
+      None
+    };
+    let message = RemoteMessage {
+      content: styled_content(&message.content),
+      ..message
+    };
+    let line = sanitize::sanitize(&render(&CONFIG.mesagisto.format, &message));
+    let lines = sanitize::split(&line, limit, CONFIG.mesagisto.max_lines);
+    match rich {
+      Some((command, text)) => {
+        let rich = Rich {
+          command,
+          text,
+          fallback: lines,
+        };
+        throttle.send(Outgoing::Rich(rich));
+      }
+      None => {
+        for line in lines {
+          throttle.send(Outgoing::Line(line));
+        }
+      }
+    }
+  }
+}
+
+fn styled_content(content: &str) -> String {
+  let config = &CONFIG.mesagisto;
+  if !config.markdown {
+    return content.to_owned();
+  }
+  let component = Component::from_markdown(content);
+  match config.color_code.chars().next() {
+    Some(code) => component.to_legacy(Lang::current(), code),
+    None => component.to_plain(Lang::current()),
+  }
+}
+
+// substitutes {sender} and {message} in a single pass, so neither can inject
+// a placeholder into the other
+fn render(format: &str, message: &RemoteMessage) -> String {
+  let mut out = String::with_capacity(format.len() + message.content.len());
+  let mut rest = format;
+  while let Some(start) = rest.find('{') {
+    out.push_str(&rest[..start]);
+    rest = &rest[start..];
+    if let Some(after) = rest.strip_prefix("{sender}") {
+      out.push_str(&message.sender);
+      rest = after;
+    } else if let Some(after) = rest.strip_prefix("{message}") {
+      out.push_str(&message.content);
+      rest = after;
+    } else {
+      out.push('{');
+      rest = &rest[1..];
+    }
+  }
+  out.push_str(rest);
+  out
+}
@@ -1,18 +1,22 @@
-use std::{
-  sync::{atomic::Ordering, Arc},
-  time::Duration,
-};
+use std::{sync::Arc, time::Duration};
 
 use color_eyre::eyre;
 use steven_protocol::protocol::{
   self,
   packet::{self, Packet},
 };
-use tokio::sync::mpsc::UnboundedSender;
 use tracing::trace;
 
 use super::PacketHandler;
-use crate::game::STEP;
+use crate::{
+  config::CONFIG,
+  game::{
+    outbound::Outbound,
+    queue::QueueSender,
+    state::{ConnState, Step},
+    tablist::TabList,
+  },
+};
 
 const TARGET: &str = "mesagisto::steps";
 
@@ -30,38 +34,67 @@ pub fn step_10() -> PacketHandler {
     }
     pass
   })
-  .endpoint(|write_tx: UnboundedSender<Packet>| async move {
-    assert!(STEP.fetch_max(10, Ordering::Relaxed) < 10);
-    step_15(write_tx).await?;
-    Ok(())
-  })
+  .endpoint(
+    |pkt: Arc<Packet>,
+     write_tx: QueueSender<Outbound>,
+     state: Arc<ConnState>,
+     tablist: Arc<TabList>| async move {
+      state.advance(Step::JoinGame)?;
+      let entity_id = match pkt.as_ref() {
+        Packet::JoinGame_WorldNames_IsHard(v) => v.entity_id,
+        Packet::JoinGame_WorldNames(v) => v.entity_id,
+        Packet::JoinGame_HashedSeed_Respawn(v) => v.entity_id,
+        Packet::JoinGame_i32(v) => v.entity_id,
+        Packet::JoinGame_i32_ViewDistance(v) => v.entity_id,
+        Packet::JoinGame_i8(v) => v.entity_id,
+        _ => return Ok(()),
+      };
+      state.set_entity_id(entity_id);
+      tablist.start();
+      step_15(write_tx, state).await?;
+      Ok(())
+    },
+  )
 }
 
-pub async fn step_15(write_tx: UnboundedSender<Packet>) -> eyre::Result<()> {
+pub async fn step_15(
+  write_tx: QueueSender<Outbound>,
+  state: Arc<ConnState>,
+) -> eyre::Result<()> {
   tokio::time::sleep(Duration::from_secs(1)).await;
-  assert!(STEP.fetch_max(15, Ordering::Relaxed) < 15);
+  state.advance(Step::ClientSettings)?;
 
   trace!(target: TARGET, "step15 C->S Client Information");
 
-  write_tx.send(
-    packet::play::serverbound::ClientSettings {
-      locale: "en_GB".to_string(),
-      view_distance: 2,
-      chat_mode: protocol::VarInt(0),
-      chat_colors: false,
-      displayed_skin_parts: 0,
-      main_hand: protocol::VarInt(0),
-    }
-    .into(),
-  )?;
+  let settings = &CONFIG.client;
+  write_tx
+    .send(
+      packet::play::serverbound::ClientSettings {
+        locale: settings.locale.to_string(),
+        view_distance: settings.view_distance,
+        chat_mode: protocol::VarInt(settings.chat_mode),
+        chat_colors: settings.chat_colors,
+        displayed_skin_parts: settings.displayed_skin_parts,
+        main_hand: protocol::VarInt(settings.main_hand),
+      }
+      .into(),
+    )
+    .await?;
   Ok(())
 }
-pub async fn step_33(write_tx: UnboundedSender<Packet>) -> eyre::Result<()> {
+pub async fn step_33(
+  write_tx: QueueSender<Outbound>,
+  state: Arc<ConnState>,
+) -> eyre::Result<()> {
+  state.advance(Step::Spawned)?;
   trace!(target: TARGET, "step33 C->S Client Command");
-  write_tx.send(
-    packet::play::serverbound::ClientStatus {
+  write_tx
+    .send(
+      packet::play::serverbound::ClientStatus {
         action_id: protocol::VarInt(0),
-    }.into()
-  )?;
+      }
+      .into(),
+    )
+    .await?;
   Ok(())
 }
\ No newline at end of file
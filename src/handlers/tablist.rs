@@ -0,0 +1,46 @@
+use std::{sync::Arc, time::Duration};
+
+use steven_protocol::protocol::packet::Packet;
+use tracing::{debug, warn};
+
+use super::{PacketHandler, TARGET};
+use crate::{
+  bridge::Bridge,
+  chat::Lang,
+  config::CONFIG,
+  game::tablist::{TabEvent, TabList},
+};
+
+pub fn tablist_handler() -> PacketHandler {
+  dptree::filter(|pkt: Arc<Packet>| {
+    matches!(pkt.as_ref(), Packet::PlayerInfo(_) | Packet::PlayerInfo_String(_))
+  })
+  .endpoint(
+    |pkt: Arc<Packet>, tablist: Arc<TabList>, bridge: Arc<dyn Bridge>| async move {
+      let events = tablist.apply(&pkt);
+      let config = &CONFIG.tablist;
+      if !config.announce || events.is_empty() {
+        return Ok(());
+      }
+      if tablist.is_filling(Duration::from_secs(config.quiet_period)) {
+        debug!(target: TARGET, "Not announcing the initial tab list: {:?}", events);
+        return Ok(());
+      }
+      let lang = Lang::current();
+      for event in events {
+        let text = match event {
+          TabEvent::Joined(player) => {
+            lang.translate("multiplayer.player.joined", &[player.shown_name().to_owned()])
+          }
+          TabEvent::Left(player) => {
+            lang.translate("multiplayer.player.left", &[player.shown_name().to_owned()])
+          }
+        };
+        if let Err(err) = bridge.notify(text).await {
+          warn!(target: TARGET, "Failed to announce tab list change: {:?}", err);
+        }
+      }
+      Ok(())
+    },
+  )
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use steven_protocol::protocol::packet::Packet;
+use tracing::warn;
+
+use super::{PacketHandler, TARGET};
+use crate::{
+  chat::{
+    kind::ChatKind,
+    tellraw::{self, Tellraw},
+    Component,
+  },
+  game::{state::ConnState, throttle::ChatThrottle},
+};
+
+/// Follows the op level the server announces for the bot, so `tellraw` is
+/// only sent when the bot may use it.
+pub fn permission_handler() -> PacketHandler {
+  dptree::filter_map(|pkt: Arc<Packet>, state: Arc<ConnState>| match pkt.as_ref() {
+    Packet::EntityStatus(v) if Some(v.entity_id) == state.entity_id() => {
+      tellraw::op_level(v.entity_status)
+    }
+    _ => None,
+  })
+  .endpoint(|level: u8, tellraw: Arc<Tellraw>| async move {
+    if tellraw.set_op_level(level) {
+      warn!(
+        target: TARGET,
+        "The bot has op level {}, /tellraw needs 2, sending remote messages as plain chat", level
+      );
+    }
+    Ok(())
+  })
+}
+
+/// Catches the server refusing `tellraw` before the refusal is relayed, and
+/// sends the refused messages as plain chat instead.
+pub fn refusal_handler() -> PacketHandler {
+  dptree::filter_map(
+    |pkt: Arc<Packet>, chat: Option<Arc<Component>>, tellraw: Arc<Tellraw>| {
+      let position = match pkt.as_ref() {
+        Packet::ServerMessage_Sender(v) => v.position,
+        Packet::ServerMessage_Position(v) => v.position,
+        _ => return None,
+      };
+      if ChatKind::from_position(position) != ChatKind::System {
+        return None;
+      }
+      tellraw.refused(chat.as_deref()?)
+    },
+  )
+  .endpoint(|fallback: Vec<String>, throttle: Arc<ChatThrottle>| async move {
+    if !fallback.is_empty() {
+      warn!(
+        target: TARGET,
+        "The server refused /tellraw, sending remote messages as plain chat from now on"
+      );
+      throttle.resend(fallback);
+    }
+    Ok(())
+  })
+}
@@ -0,0 +1,138 @@
+use std::{
+  collections::HashMap,
+  fs,
+  io::Write,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use color_eyre::eyre::{self, WrapErr};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use steven_protocol::protocol::mojang::{AuthService, Profile};
+
+pub const LOCATION: &str = "config/credentials.yml";
+
+// every binding logs in on its own, the file is shared between them
+static WRITE_LOCK: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Credential {
+  pub username: String,
+  pub id: String,
+  pub access_token: String,
+  // yggdrasil only, tokens are bound to the client token they were issued for
+  pub client_token: Option<String>,
+  // microsoft only, the oauth refresh token
+  pub refresh_token: Option<String>,
+  // unix timestamp in seconds
+  pub expires_at: Option<i64>,
+}
+
+impl Credential {
+  pub fn is_expired(&self) -> bool {
+    match self.expires_at {
+      // leave a minute of margin for the join request itself
+      Some(expires_at) => chrono::Utc::now().timestamp() + 60 >= expires_at,
+      None => false,
+    }
+  }
+
+  pub fn to_profile(&self, auth_service: Arc<AuthService>) -> Profile {
+    Profile {
+      username: self.username.clone(),
+      id: self.id.clone(),
+      access_token: self.access_token.clone(),
+      auth_service,
+    }
+  }
+}
+
+#[derive(Default)]
+pub struct CredentialCache {
+  path: PathBuf,
+  entries: HashMap<String, Credential>,
+}
+
+impl CredentialCache {
+  pub fn load() -> eyre::Result<Self> {
+    Self::load_from(LOCATION)
+  }
+
+  pub fn load_from(path: impl AsRef<Path>) -> eyre::Result<Self> {
+    let path = path.as_ref().to_owned();
+    let entries = match fs::read(&path) {
+      Ok(content) => serde_yaml::from_slice(&content)
+        .wrap_err_with(|| format!("corrupted credential cache {}", path.display()))?,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+      Err(err) => return Err(err).wrap_err_with(|| format!("failed to read {}", path.display())),
+    };
+    Ok(Self { path, entries })
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Credential> {
+    self.entries.get(key)
+  }
+
+  pub fn insert(&mut self, key: String, credential: Credential) -> eyre::Result<()> {
+    self.update(|entries| {
+      entries.insert(key, credential);
+      true
+    })?;
+    Ok(())
+  }
+
+  /// Drops every entry holding `access_token`, returns whether any was found.
+  pub fn invalidate(&mut self, access_token: &str) -> eyre::Result<bool> {
+    self.update(|entries| {
+      let before = entries.len();
+      entries.retain(|_, credential| credential.access_token != access_token);
+      entries.len() != before
+    })
+  }
+
+  // Applies the change to what is on disk right now, another binding may have
+  // saved its own login since this cache was loaded.
+  fn update(
+    &mut self,
+    change: impl FnOnce(&mut HashMap<String, Credential>) -> bool,
+  ) -> eyre::Result<bool> {
+    let _guard = WRITE_LOCK.lock().unwrap();
+    self.entries = Self::load_from(&self.path)?.entries;
+    let changed = change(&mut self.entries);
+    if changed {
+      self.save()?;
+    }
+    Ok(changed)
+  }
+
+  fn save(&self) -> eyre::Result<()> {
+    if let Some(parent) = self.path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    let content = serde_yaml::to_string(&self.entries)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::OpenOptionsExt;
+      options.mode(0o600);
+    }
+    let mut file = options
+      .open(&self.path)
+      .wrap_err_with(|| format!("failed to write {}", self.path.display()))?;
+    // the mode above only applies to newly created files
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content.as_bytes())?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+pub fn temp_location() -> PathBuf {
+  std::env::temp_dir().join(format!("mesagisto-credentials-{}.yml", uuid::Uuid::new_v4()))
+}
@@ -0,0 +1,528 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use color_eyre::eyre::{self, bail, eyre, WrapErr};
+use serde::Deserialize;
+use serde_json::json;
+use steven_protocol::protocol::mojang::{AuthService, Profile};
+use tracing::{debug, info, warn};
+
+use super::{
+  cache::{self, Credential, CredentialCache},
+  yggdrasil, TARGET,
+};
+use crate::config::MicrosoftConfig;
+
+const SCOPE: &str = "XboxLive.signin offline_access";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+#[derive(Deserialize, Debug)]
+struct DeviceCode {
+  device_code: String,
+  user_code: String,
+  verification_uri: String,
+  expires_in: u64,
+  interval: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct OAuthToken {
+  refresh_token: String,
+  access_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OAuthError {
+  error: String,
+  error_description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct XboxToken {
+  token: String,
+  display_claims: DisplayClaims,
+}
+
+#[derive(Deserialize, Debug)]
+struct DisplayClaims {
+  xui: Vec<UserHash>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UserHash {
+  uhs: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct XstsError {
+  x_err: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct MinecraftToken {
+  access_token: String,
+  expires_in: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct MinecraftProfile {
+  id: String,
+  name: String,
+}
+
+pub struct Microsoft<'a> {
+  config: &'a MicrosoftConfig,
+  auth_service: Arc<AuthService>,
+  http: reqwest::Client,
+  cache: PathBuf,
+}
+
+impl<'a> Microsoft<'a> {
+  pub fn new(config: &'a MicrosoftConfig) -> eyre::Result<Self> {
+    Ok(Self {
+      config,
+      auth_service: Arc::new(yggdrasil::auth_service(
+        yggdrasil::MOJANG_AUTH_SERVER,
+        &config.session_server,
+      )?),
+      http: reqwest::Client::new(),
+      cache: PathBuf::from(cache::LOCATION),
+    })
+  }
+
+  #[cfg(test)]
+  fn with_cache(mut self, path: impl Into<PathBuf>) -> Self {
+    self.cache = path.into();
+    self
+  }
+
+  /// `label` only tells cached accounts apart, the account itself is chosen
+  /// by whoever enters the device code.
+  pub async fn profile(&self, label: &str) -> eyre::Result<Profile> {
+    let key = format!("{}@microsoft", label);
+    let mut cache = CredentialCache::load_from(&self.cache)?;
+
+    if let Some(credential) = cache.get(&key).cloned() {
+      if !credential.is_expired() && self.validate(&credential.access_token).await {
+        info!(target: TARGET, "Reusing cached session of {}", credential.username);
+        return Ok(credential.to_profile(self.auth_service.clone()));
+      }
+      if let Some(refresh_token) = &credential.refresh_token {
+        match self.refresh(refresh_token).await {
+          Ok(credential) => {
+            info!(target: TARGET, "Refreshed cached session of {}", credential.username);
+            cache.insert(key, credential.clone())?;
+            return Ok(credential.to_profile(self.auth_service.clone()));
+          }
+          Err(err) => warn!(target: TARGET, "Failed to refresh cached session: {:?}", err),
+        }
+      }
+    }
+
+    let oauth = self.device_code_login().await?;
+    let credential = self.minecraft_credential(oauth).await?;
+    cache.insert(key, credential.clone())?;
+    Ok(credential.to_profile(self.auth_service.clone()))
+  }
+
+  async fn refresh(&self, refresh_token: &str) -> eyre::Result<Credential> {
+    let oauth = self.refresh_oauth_token(refresh_token).await?;
+    self.minecraft_credential(oauth).await
+  }
+
+  // as with Yggdrasil, a session which cannot be checked is refreshed
+  async fn validate(&self, access_token: &str) -> bool {
+    match self.try_validate(access_token).await {
+      Ok(valid) => valid,
+      Err(err) => {
+        warn!(target: TARGET, "Failed to validate cached session: {:?}", err);
+        false
+      }
+    }
+  }
+
+  async fn try_validate(&self, access_token: &str) -> eyre::Result<bool> {
+    let resp = self
+      .http
+      .get(self.url(&self.config.minecraft_server, "minecraft/profile")?)
+      .bearer_auth(access_token)
+      .send()
+      .await?;
+    Ok(resp.status().is_success())
+  }
+
+  async fn device_code_login(&self) -> eyre::Result<OAuthToken> {
+    let code: DeviceCode = self
+      .http
+      .post(self.url(&self.config.oauth_server, "consumers/oauth2/v2.0/devicecode")?)
+      .form(&[("client_id", self.config.client_id.as_str()), ("scope", SCOPE)])
+      .send()
+      .await?
+      .error_for_status()
+      .wrap_err("failed to request a Microsoft device code")?
+      .json()
+      .await?;
+    warn!(
+      target: TARGET,
+      "To sign in the bot, open {} in a browser and enter the code {}",
+      code.verification_uri,
+      code.user_code
+    );
+
+    let token_url = self.url(&self.config.oauth_server, "consumers/oauth2/v2.0/token")?;
+    let mut interval = Duration::from_secs(code.interval.max(1));
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(code.expires_in);
+    while tokio::time::Instant::now() < deadline {
+      tokio::time::sleep(interval).await;
+      let resp = self
+        .http
+        .post(token_url.clone())
+        .form(&[
+          ("grant_type", DEVICE_CODE_GRANT),
+          ("client_id", self.config.client_id.as_str()),
+          ("device_code", code.device_code.as_str()),
+        ])
+        .send()
+        .await?;
+      if resp.status().is_success() {
+        info!(target: TARGET, "Microsoft account authorized");
+        return Ok(resp.json().await?);
+      }
+      let err: OAuthError = resp.json().await?;
+      match err.error.as_str() {
+        "authorization_pending" => continue,
+        "slow_down" => interval += Duration::from_secs(5),
+        _ => bail!(
+          "Microsoft device code login failed: {} {}",
+          err.error,
+          err.error_description.unwrap_or_default()
+        ),
+      }
+    }
+    bail!("Microsoft device code expired before it was entered")
+  }
+
+  async fn refresh_oauth_token(&self, refresh_token: &str) -> eyre::Result<OAuthToken> {
+    let token = self
+      .http
+      .post(self.url(&self.config.oauth_server, "consumers/oauth2/v2.0/token")?)
+      .form(&[
+        ("grant_type", "refresh_token"),
+        ("client_id", self.config.client_id.as_str()),
+        ("refresh_token", refresh_token),
+        ("scope", SCOPE),
+      ])
+      .send()
+      .await?
+      .error_for_status()
+      .wrap_err("failed to refresh the Microsoft token")?
+      .json()
+      .await?;
+    Ok(token)
+  }
+
+  async fn minecraft_credential(&self, oauth: OAuthToken) -> eyre::Result<Credential> {
+    let xbl: XboxToken = self
+      .http
+      .post(self.url(&self.config.xbox_server, "user/authenticate")?)
+      .json(&json!({
+        "Properties": {
+          "AuthMethod": "RPS",
+          "SiteName": "user.auth.xboxlive.com",
+          "RpsTicket": format!("d={}", oauth.access_token)
+        },
+        "RelyingParty": "http://auth.xboxlive.com",
+        "TokenType": "JWT"
+      }))
+      .send()
+      .await?
+      .error_for_status()
+      .wrap_err("Xbox Live authentication failed")?
+      .json()
+      .await?;
+    debug!(target: TARGET, "Xbox Live authenticated");
+
+    let resp = self
+      .http
+      .post(self.url(&self.config.xsts_server, "xsts/authorize")?)
+      .json(&json!({
+        "Properties": {
+          "SandboxId": "RETAIL",
+          "UserTokens": [xbl.token]
+        },
+        "RelyingParty": "rp://api.minecraftservices.com/",
+        "TokenType": "JWT"
+      }))
+      .send()
+      .await?;
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+      let err: XstsError = resp.json().await?;
+      bail!(match err.x_err {
+        2148916233 => "the Microsoft account has no Xbox account".to_owned(),
+        2148916235 => "Xbox Live is not available in the account's country".to_owned(),
+        2148916236 | 2148916237 => "the account needs adult verification".to_owned(),
+        2148916238 => "the account is a child account and must be added to a family".to_owned(),
+        code => format!("XSTS authorization failed with XErr {}", code),
+      });
+    }
+    let xsts: XboxToken = resp
+      .error_for_status()
+      .wrap_err("XSTS authorization failed")?
+      .json()
+      .await?;
+    let user_hash = &xsts
+      .display_claims
+      .xui
+      .first()
+      .ok_or_else(|| eyre!("XSTS response has no user hash"))?
+      .uhs;
+    debug!(target: TARGET, "XSTS authorized");
+
+    let mc: MinecraftToken = self
+      .http
+      .post(self.url(&self.config.minecraft_server, "authentication/login_with_xbox")?)
+      .json(&json!({
+        "identityToken": format!("XBL3.0 x={};{}", user_hash, xsts.token)
+      }))
+      .send()
+      .await?
+      .error_for_status()
+      .wrap_err("Minecraft services rejected the Xbox token")?
+      .json()
+      .await?;
+
+    let resp = self
+      .http
+      .get(self.url(&self.config.minecraft_server, "minecraft/profile")?)
+      .bearer_auth(&mc.access_token)
+      .send()
+      .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+      bail!("the Microsoft account does not own Minecraft Java Edition");
+    }
+    let profile: MinecraftProfile = resp
+      .error_for_status()
+      .wrap_err("failed to fetch the Minecraft profile")?
+      .json()
+      .await?;
+
+    Ok(Credential {
+      username: profile.name,
+      id: profile.id,
+      access_token: mc.access_token,
+      client_token: None,
+      refresh_token: Some(oauth.refresh_token),
+      expires_at: Some(chrono::Utc::now().timestamp() + mc.expires_in),
+    })
+  }
+
+  fn url(&self, base: &str, path: &str) -> eyre::Result<reqwest::Url> {
+    Ok(yggdrasil::parse_base_url(base)?.join(path)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use wiremock::{
+    matchers::{body_partial_json, body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+  };
+
+  use super::*;
+
+  const PROFILE_ID: &str = "2b0b5f2e1e7b4b1f9a0c4a3f6a3e1d2c";
+
+  fn config(server: &MockServer) -> MicrosoftConfig {
+    let uri = arcstr::ArcStr::from(server.uri());
+    MicrosoftConfig {
+      client_id: arcstr::literal!("client"),
+      oauth_server: uri.clone(),
+      xbox_server: uri.clone(),
+      xsts_server: uri.clone(),
+      minecraft_server: uri.clone(),
+      session_server: uri,
+    }
+  }
+
+  async fn mock(server: &MockServer, verb: &str, endpoint: &str, response: ResponseTemplate) {
+    Mock::given(method(verb))
+      .and(path(endpoint))
+      .respond_with(response)
+      .mount(server)
+      .await;
+  }
+
+  fn xbox_token(token: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+      "Token": token,
+      "DisplayClaims": { "xui": [{ "uhs": "hash" }] }
+    }))
+  }
+
+  fn oauth_token(access_token: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+      "access_token": access_token,
+      "refresh_token": "refresh",
+    }))
+  }
+
+  fn oauth_error(error: &str) -> ResponseTemplate {
+    ResponseTemplate::new(400).set_body_json(json!({ "error": error }))
+  }
+
+  async fn mock_device_code(server: &MockServer) {
+    let code = json!({
+      "device_code": "device",
+      "user_code": "ABCD-EFGH",
+      "verification_uri": "https://microsoft.com/link",
+      "expires_in": 60,
+      "interval": 1,
+    });
+    let code = ResponseTemplate::new(200).set_body_json(code);
+    mock(server, "POST", "/consumers/oauth2/v2.0/devicecode", code).await;
+  }
+
+  // everything after the oauth token, up to the Minecraft profile
+  async fn mock_xbox_chain(server: &MockServer) {
+    mock(server, "POST", "/user/authenticate", xbox_token("xbl")).await;
+    Mock::given(method("POST"))
+      .and(path("/xsts/authorize"))
+      .and(body_partial_json(json!({ "Properties": { "UserTokens": ["xbl"] } })))
+      .respond_with(xbox_token("xsts"))
+      .mount(server)
+      .await;
+    Mock::given(method("POST"))
+      .and(path("/authentication/login_with_xbox"))
+      .and(body_partial_json(json!({ "identityToken": "XBL3.0 x=hash;xsts" })))
+      .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+        "access_token": "minecraft",
+        "expires_in": 86400,
+      })))
+      .mount(server)
+      .await;
+  }
+
+  async fn mock_profile(server: &MockServer) {
+    let profile = json!({ "id": PROFILE_ID, "name": "Bot" });
+    let profile = ResponseTemplate::new(200).set_body_json(profile);
+    mock(server, "GET", "/minecraft/profile", profile).await;
+  }
+
+  #[tokio::test]
+  async fn signs_in_with_a_device_code() {
+    let server = MockServer::start().await;
+    mock_device_code(&server).await;
+    // the code is entered after the first poll
+    Mock::given(method("POST"))
+      .and(path("/consumers/oauth2/v2.0/token"))
+      .and(body_string_contains("device_code=device"))
+      .respond_with(oauth_error("authorization_pending"))
+      .up_to_n_times(1)
+      .mount(&server)
+      .await;
+    Mock::given(method("POST"))
+      .and(path("/consumers/oauth2/v2.0/token"))
+      .and(body_string_contains("device_code=device"))
+      .respond_with(oauth_token("oauth"))
+      .expect(1)
+      .mount(&server)
+      .await;
+    mock_xbox_chain(&server).await;
+    mock_profile(&server).await;
+    let config = config(&server);
+    let cache = cache::temp_location();
+    let microsoft = Microsoft::new(&config).unwrap().with_cache(&cache);
+
+    let profile = microsoft.profile("bot").await.unwrap();
+    assert_eq!(profile.username, "Bot");
+    assert_eq!(profile.id, PROFILE_ID);
+    assert_eq!(profile.access_token, "minecraft");
+
+    let cached = CredentialCache::load_from(&cache).unwrap();
+    let cached = cached.get("bot@microsoft").unwrap();
+    assert_eq!(cached.refresh_token.as_deref(), Some("refresh"));
+    assert!(!cached.is_expired());
+    std::fs::remove_file(cache).unwrap();
+  }
+
+  #[tokio::test]
+  async fn refreshes_an_expired_session() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+      .and(path("/consumers/oauth2/v2.0/token"))
+      .and(body_string_contains("grant_type=refresh_token"))
+      .and(body_string_contains("refresh_token=old"))
+      .respond_with(oauth_token("oauth"))
+      .expect(1)
+      .mount(&server)
+      .await;
+    mock_xbox_chain(&server).await;
+    mock_profile(&server).await;
+    let config = config(&server);
+    let cache = cache::temp_location();
+    let credential = Credential {
+      username: String::from("Bot"),
+      id: String::from(PROFILE_ID),
+      access_token: String::from("expired"),
+      client_token: None,
+      refresh_token: Some(String::from("old")),
+      expires_at: Some(0),
+    };
+    let mut cached = CredentialCache::load_from(&cache).unwrap();
+    cached.insert(String::from("bot@microsoft"), credential).unwrap();
+    let microsoft = Microsoft::new(&config).unwrap().with_cache(&cache);
+
+    let profile = microsoft.profile("bot").await.unwrap();
+    assert_eq!(profile.access_token, "minecraft");
+    std::fs::remove_file(cache).unwrap();
+  }
+
+  #[tokio::test]
+  async fn fails_when_the_code_is_declined() {
+    let server = MockServer::start().await;
+    mock_device_code(&server).await;
+    let declined = oauth_error("authorization_declined");
+    mock(&server, "POST", "/consumers/oauth2/v2.0/token", declined).await;
+    let config = config(&server);
+    let microsoft = Microsoft::new(&config)
+      .unwrap()
+      .with_cache(cache::temp_location());
+
+    let err = microsoft.profile("bot").await.unwrap_err();
+    assert!(err.to_string().contains("authorization_declined"), "{}", err);
+  }
+
+  #[tokio::test]
+  async fn explains_xsts_errors() {
+    let server = MockServer::start().await;
+    mock_device_code(&server).await;
+    mock(&server, "POST", "/consumers/oauth2/v2.0/token", oauth_token("oauth")).await;
+    mock(&server, "POST", "/user/authenticate", xbox_token("xbl")).await;
+    let no_xbox = ResponseTemplate::new(401).set_body_json(json!({ "XErr": 2148916233u64 }));
+    mock(&server, "POST", "/xsts/authorize", no_xbox).await;
+    let config = config(&server);
+    let microsoft = Microsoft::new(&config)
+      .unwrap()
+      .with_cache(cache::temp_location());
+
+    let err = microsoft.profile("bot").await.unwrap_err();
+    assert!(err.to_string().contains("no Xbox account"), "{}", err);
+  }
+
+  #[tokio::test]
+  async fn fails_for_accounts_without_minecraft() {
+    let server = MockServer::start().await;
+    mock_device_code(&server).await;
+    mock(&server, "POST", "/consumers/oauth2/v2.0/token", oauth_token("oauth")).await;
+    mock_xbox_chain(&server).await;
+    mock(&server, "GET", "/minecraft/profile", ResponseTemplate::new(404)).await;
+    let config = config(&server);
+    let microsoft = Microsoft::new(&config)
+      .unwrap()
+      .with_cache(cache::temp_location());
+
+    let err = microsoft.profile("bot").await.unwrap_err();
+    assert!(err.to_string().contains("does not own"), "{}", err);
+  }
+}
@@ -0,0 +1,124 @@
+mod cache;
+mod microsoft;
+mod offline;
+mod yggdrasil;
+
+use color_eyre::eyre;
+use steven_protocol::protocol::{self, mojang::Profile};
+use tracing::{info, warn};
+
+use crate::config::{AccountConfig, AccountMode};
+
+const TARGET: &str = "mesagisto::login";
+
+pub async fn bot_user(account: &AccountConfig) -> eyre::Result<Profile> {
+  let profile = match account.mode {
+    AccountMode::Offline => offline::profile(&account.username),
+    AccountMode::Yggdrasil => {
+      yggdrasil::Yggdrasil::new(
+        &account.yggdrasil.auth_server,
+        &account.yggdrasil.session_server,
+      )?
+      .profile(&account.username, &account.password)
+      .await?
+    }
+    AccountMode::AuthlibInjector => {
+      yggdrasil::Yggdrasil::new(
+        &account.authlib_injector.auth_server,
+        &account.authlib_injector.session_server,
+      )?
+      .profile(&account.username, &account.password)
+      .await?
+    }
+    AccountMode::Microsoft => {
+      microsoft::Microsoft::new(&account.microsoft)?
+        .profile(&account.username)
+        .await?
+    }
+  };
+  info!(
+    target: TARGET,
+    "Logged in as {} ({}) using {:?} account",
+    profile.username,
+    profile.id,
+    account.mode
+  );
+  Ok(profile)
+}
+
+/// Whether `join_server` failed because the session server no longer accepts
+/// the access token, as opposed to not being reachable at all.
+pub fn is_invalid_session(err: &protocol::Error) -> bool {
+  match err {
+    // the session server answers with an error object, which the profile
+    // turns into "{error}: {errorMessage}"
+    protocol::Error::Err(message) => message
+      .split(':')
+      .next()
+      .map_or(false, |error| error.trim() == "ForbiddenOperationException"),
+    // a session server without an error object only has the status to go by
+    protocol::Error::Reqwest(err) => err.status().map(|status| status.as_u16()) == Some(403),
+    _ => false,
+  }
+}
+
+/// Forgets the cached session of `profile`, so the next `bot_user` call
+/// performs a refresh or a full login.
+pub fn invalidate_session(profile: &Profile) {
+  if profile.access_token.is_empty() {
+    return;
+  }
+  match cache::CredentialCache::load().and_then(|mut cache| cache.invalidate(&profile.access_token))
+  {
+    Ok(true) => warn!(target: TARGET, "Invalidated cached session of {}", profile.username),
+    Ok(false) => {}
+    Err(err) => warn!(target: TARGET, "Failed to invalidate cached session: {:?}", err),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+  };
+
+  use super::*;
+
+  #[test]
+  fn only_rejected_sessions_are_invalid() {
+    let rejected = protocol::Error::Err(String::from(
+      "ForbiddenOperationException: Invalid token.",
+    ));
+    assert!(is_invalid_session(&rejected));
+    let unreachable = protocol::Error::IOError(std::io::ErrorKind::TimedOut.into());
+    assert!(!is_invalid_session(&unreachable));
+    // neither a port nor a message which happens to mention the status
+    for message in [
+      "Failed to reach sessionserver.example.com:4031",
+      "Invalid token, 403 attempts left",
+      "IllegalArgumentException: ForbiddenOperationException",
+    ] {
+      assert!(!is_invalid_session(&protocol::Error::Err(message.into())), "{}", message);
+    }
+  }
+
+  #[tokio::test]
+  async fn forbidden_status_is_an_invalid_session() {
+    let server = MockServer::start().await;
+    for (status, invalid) in [(403, true), (404, false), (503, false)] {
+      Mock::given(method("POST"))
+        .and(path(format!("/{}", status)))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(&server)
+        .await;
+      let err = reqwest::Client::new()
+        .post(format!("{}/{}", server.uri(), status))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .unwrap_err();
+      assert_eq!(is_invalid_session(&protocol::Error::Reqwest(err)), invalid, "{}", status);
+    }
+  }
+}
@@ -0,0 +1,42 @@
+use md5::{Digest, Md5};
+use steven_protocol::protocol::mojang::Profile;
+use uuid::{Builder, Uuid};
+
+use super::yggdrasil;
+
+/// Same as `UUID.nameUUIDFromBytes("OfflinePlayer:<name>")` on the vanilla
+/// server, so the bot keeps its inventory and permissions between restarts.
+pub fn uuid(username: &str) -> Uuid {
+  let digest = Md5::digest(format!("OfflinePlayer:{}", username).as_bytes());
+  Builder::from_md5_bytes(digest.into()).into_uuid()
+}
+
+pub fn profile(username: &str) -> Profile {
+  // offline servers never send an EncryptionRequest, so the auth service is
+  // only there to satisfy the profile and will not be contacted
+  Profile {
+    username: username.to_owned(),
+    id: uuid(username).as_simple().to_string(),
+    access_token: String::new(),
+    auth_service: yggdrasil::mojang_auth_service(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_the_vanilla_offline_uuid() {
+    assert_eq!(uuid("Notch").to_string(), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+    assert_eq!(uuid("Notch").get_version_num(), 3);
+  }
+
+  #[test]
+  fn profile_uses_the_offline_uuid() {
+    let profile = profile("MesagistoBot");
+    assert_eq!(profile.username, "MesagistoBot");
+    assert_eq!(profile.id, "aa06ff2e35003433aca856ca73cccd3d");
+    assert!(profile.access_token.is_empty());
+  }
+}
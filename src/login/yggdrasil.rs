@@ -0,0 +1,273 @@
+use std::{path::PathBuf, sync::Arc};
+
+use color_eyre::eyre::{self, WrapErr};
+use serde::Deserialize;
+use serde_json::json;
+use steven_protocol::protocol::mojang::{AuthService, Profile};
+use tracing::{info, warn};
+
+use super::{
+  cache::{self, Credential, CredentialCache},
+  TARGET,
+};
+
+pub const MOJANG_AUTH_SERVER: &str = "https://authserver.mojang.com/";
+pub const MOJANG_SESSION_SERVER: &str = "https://sessionserver.mojang.com/";
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RefreshResponse {
+  access_token: String,
+  client_token: String,
+  selected_profile: SelectedProfile,
+}
+
+#[derive(Deserialize, Debug)]
+struct SelectedProfile {
+  id: String,
+  name: String,
+}
+
+/// Works for both Mojang and authlib-injector servers, the latter being a
+/// Yggdrasil implementation hosted under `{api_root}/authserver/` and
+/// `{api_root}/sessionserver/`.
+pub struct Yggdrasil {
+  auth_url: reqwest::Url,
+  auth_service: Arc<AuthService>,
+  http: reqwest::Client,
+  cache: PathBuf,
+}
+
+impl Yggdrasil {
+  pub fn new(auth_server: &str, session_server: &str) -> eyre::Result<Self> {
+    Ok(Self {
+      auth_url: parse_base_url(auth_server)?,
+      auth_service: Arc::new(auth_service(auth_server, session_server)?),
+      http: reqwest::Client::new(),
+      cache: PathBuf::from(cache::LOCATION),
+    })
+  }
+
+  #[cfg(test)]
+  fn with_cache(mut self, path: impl Into<PathBuf>) -> Self {
+    self.cache = path.into();
+    self
+  }
+
+  pub async fn profile(&self, username: &str, password: &str) -> eyre::Result<Profile> {
+    let key = format!("{}@{}", username, self.auth_url);
+    let mut cache = CredentialCache::load_from(&self.cache)?;
+    let cached = cache.get(&key).cloned();
+
+    if let Some(credential) = &cached {
+      if self.validate(credential).await {
+        info!(target: TARGET, "Reusing cached session of {}", credential.username);
+        return Ok(credential.to_profile(self.auth_service.clone()));
+      }
+      match self.refresh(credential).await {
+        Ok(credential) => {
+          info!(target: TARGET, "Refreshed cached session of {}", credential.username);
+          cache.insert(key, credential.clone())?;
+          return Ok(credential.to_profile(self.auth_service.clone()));
+        }
+        Err(err) => warn!(target: TARGET, "Failed to refresh cached session: {:?}", err),
+      }
+    }
+
+    let client_token = cached
+      .and_then(|credential| credential.client_token)
+      .unwrap_or_else(|| uuid::Uuid::new_v4().as_simple().to_string());
+    let profile =
+      Profile::login_with_auth(username, password, &client_token, self.auth_service.clone())
+        .await
+        .wrap_err_with(|| format!("failed to login {} through {}", username, self.auth_url))?;
+    cache.insert(
+      key,
+      Credential {
+        username: profile.username.clone(),
+        id: profile.id.clone(),
+        access_token: profile.access_token.clone(),
+        client_token: Some(client_token),
+        refresh_token: None,
+        expires_at: None,
+      },
+    )?;
+    Ok(profile)
+  }
+
+  // a session which cannot be checked right now is refreshed like an invalid
+  // one, the refresh or login after it fails on its own if the server is down
+  async fn validate(&self, credential: &Credential) -> bool {
+    match self.try_validate(credential).await {
+      Ok(valid) => valid,
+      Err(err) => {
+        warn!(target: TARGET, "Failed to validate cached session: {:?}", err);
+        false
+      }
+    }
+  }
+
+  async fn try_validate(&self, credential: &Credential) -> eyre::Result<bool> {
+    let resp = self
+      .http
+      .post(self.auth_url.join("validate")?)
+      .json(&json!({
+        "accessToken": credential.access_token,
+        "clientToken": credential.client_token,
+      }))
+      .send()
+      .await?;
+    Ok(resp.status().is_success())
+  }
+
+  async fn refresh(&self, credential: &Credential) -> eyre::Result<Credential> {
+    let resp: RefreshResponse = self
+      .http
+      .post(self.auth_url.join("refresh")?)
+      .json(&json!({
+        "accessToken": credential.access_token,
+        "clientToken": credential.client_token,
+      }))
+      .send()
+      .await?
+      .error_for_status()?
+      .json()
+      .await?;
+    Ok(Credential {
+      username: resp.selected_profile.name,
+      id: resp.selected_profile.id,
+      access_token: resp.access_token,
+      client_token: Some(resp.client_token),
+      refresh_token: None,
+      expires_at: None,
+    })
+  }
+}
+
+pub fn mojang_auth_service() -> Arc<AuthService> {
+  Arc::new(auth_service(MOJANG_AUTH_SERVER, MOJANG_SESSION_SERVER).unwrap())
+}
+
+pub fn auth_service(auth_server: &str, session_server: &str) -> eyre::Result<AuthService> {
+  let auth_url = parse_base_url(auth_server)?;
+  let session_url = parse_base_url(session_server)?;
+  Ok(AuthService::new(auth_url, session_url))
+}
+
+// Url::join drops the last path segment unless the base ends with a slash
+pub fn parse_base_url(url: &str) -> eyre::Result<reqwest::Url> {
+  let url = if url.ends_with('/') {
+    url.to_owned()
+  } else {
+    format!("{}/", url)
+  };
+  reqwest::Url::parse(&url).wrap_err_with(|| format!("invalid url `{}`", url))
+}
+
+#[cfg(test)]
+mod tests {
+  use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+  };
+
+  use super::*;
+
+  const AUTH: &str = "/api/yggdrasil/authserver";
+  const SESSION: &str = "/api/yggdrasil/sessionserver";
+
+  fn yggdrasil(server: &MockServer) -> (Yggdrasil, PathBuf) {
+    let cache = cache::temp_location();
+    let yggdrasil = Yggdrasil::new(
+      &format!("{}{}", server.uri(), AUTH),
+      &format!("{}{}", server.uri(), SESSION),
+    )
+    .unwrap()
+    .with_cache(&cache);
+    (yggdrasil, cache)
+  }
+
+  fn session(access_token: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+      "accessToken": access_token,
+      "clientToken": "client",
+      "selectedProfile": { "id": "2b0b5f2e1e7b4b1f9a0c4a3f6a3e1d2c", "name": "Bot" }
+    }))
+  }
+
+  async fn mock(server: &MockServer, endpoint: &str, response: ResponseTemplate, times: u64) {
+    Mock::given(method("POST"))
+      .and(path(format!("{}/{}", AUTH, endpoint)))
+      .respond_with(response)
+      .expect(times)
+      .mount(server)
+      .await;
+  }
+
+  #[tokio::test]
+  async fn logs_in_then_reuses_the_cached_session() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+      .and(path(format!("{}/authenticate", AUTH)))
+      .and(body_partial_json(json!({
+        "username": "bot@example.com",
+        "password": "secret"
+      })))
+      .respond_with(session("first"))
+      .expect(1)
+      .mount(&server)
+      .await;
+    mock(&server, "validate", ResponseTemplate::new(204), 1).await;
+    let (yggdrasil, cache) = yggdrasil(&server);
+
+    let profile = yggdrasil.profile("bot@example.com", "secret").await.unwrap();
+    assert_eq!(profile.username, "Bot");
+    assert_eq!(profile.access_token, "first");
+
+    let profile = yggdrasil.profile("bot@example.com", "secret").await.unwrap();
+    assert_eq!(profile.access_token, "first");
+    std::fs::remove_file(cache).unwrap();
+  }
+
+  #[tokio::test]
+  async fn refreshes_an_invalid_session() {
+    let server = MockServer::start().await;
+    mock(&server, "authenticate", session("first"), 1).await;
+    mock(&server, "validate", ResponseTemplate::new(403), 1).await;
+    mock(&server, "refresh", session("second"), 1).await;
+    let (yggdrasil, cache) = yggdrasil(&server);
+
+    yggdrasil.profile("bot@example.com", "secret").await.unwrap();
+    let profile = yggdrasil.profile("bot@example.com", "secret").await.unwrap();
+    assert_eq!(profile.access_token, "second");
+    std::fs::remove_file(cache).unwrap();
+  }
+
+  #[tokio::test]
+  async fn logs_in_again_when_the_refresh_fails() {
+    let server = MockServer::start().await;
+    mock(&server, "authenticate", session("first"), 2).await;
+    mock(&server, "validate", ResponseTemplate::new(403), 1).await;
+    mock(&server, "refresh", ResponseTemplate::new(403), 1).await;
+    let (yggdrasil, cache) = yggdrasil(&server);
+
+    yggdrasil.profile("bot@example.com", "secret").await.unwrap();
+    let profile = yggdrasil.profile("bot@example.com", "secret").await.unwrap();
+    assert_eq!(profile.access_token, "first");
+    std::fs::remove_file(cache).unwrap();
+  }
+
+  #[tokio::test]
+  async fn fails_on_wrong_credentials() {
+    let server = MockServer::start().await;
+    let error = json!({
+      "error": "ForbiddenOperationException",
+      "errorMessage": "Invalid credentials. Invalid username or password."
+    });
+    mock(&server, "authenticate", ResponseTemplate::new(403).set_body_json(error), 1).await;
+    let (yggdrasil, cache) = yggdrasil(&server);
+
+    assert!(yggdrasil.profile("bot@example.com", "wrong").await.is_err());
+    assert!(!cache.exists());
+  }
+}
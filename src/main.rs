@@ -1,15 +1,27 @@
+#[macro_use]
+extern crate automatic_config;
+#[macro_use]
+extern crate educe;
+
+mod bridge;
+pub mod chat;
+mod cli;
+pub mod config;
 pub mod data;
 pub mod exts;
 pub mod game;
 mod handlers;
 mod log;
 mod login;
+mod supervisor;
 
 use color_eyre::eyre;
-use steven_protocol::protocol::packet::Packet;
-
+use tracing::{info, warn};
 
-use crate::{game::Client, login::bot_user};
+use crate::{
+  chat::Lang,
+  config::{Config, CONFIG},
+};
 
 const TARGET: &str = "mesagisto";
 
@@ -25,25 +37,29 @@ async fn main() -> eyre::Result<()> {
 
   // enable_network_debug();
   self::log::init();
-  let client = Client::new(
-    340,
-    bot_user().await?,
-  );
-  let mut server = client.connect_to("127.0.0.1:25565").await?;
-  let read_rx = server.read_queue.take().unwrap();
-  let write = server.conn.take().unwrap();
-  let (write_tx, write_rx) = tokio::sync::mpsc::unbounded_channel::<Packet>();
-  tokio::spawn(async move {
-    crate::handlers::write::handler(write, write_rx)
-      .await
-      .unwrap();
-  });
-  let clone_write_tx = write_tx.clone();
-  tokio::spawn(async move {
-    crate::handlers::init(client, read_rx, clone_write_tx)
-      .await
-      .unwrap();
-  });
-  tokio::signal::ctrl_c().await?;
+  Config::reload().await?;
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  if let [flag, lines @ ..] = args.as_slice() {
+    match flag.as_str() {
+      "--parse-chat" => return cli::parse_chat(lines),
+      other => eyre::bail!("unknown argument `{}`", other),
+    }
+  }
+  if !CONFIG.enable {
+    warn!(target: TARGET, "Mesagisto-Bot is not enabled and is about to exit the program.");
+    warn!(target: TARGET, "To enable it, please modify the configuration file config/mc.yml.");
+    CONFIG.save().await?;
+    return Ok(());
+  }
+  CONFIG.validate()?;
+  CONFIG.save().await?;
+  Lang::init(&CONFIG.chat.lang)?;
+
+  bridge::mesagisto::init(&CONFIG.mesagisto).await?;
+
+  tokio::select! {
+    result = supervisor::run_all(&CONFIG.bindings, &CONFIG.reconnect) => result?,
+    _ = tokio::signal::ctrl_c() => info!(target: TARGET, "Received Ctrl-C, shutting down"),
+  }
   Ok(())
 }
@@ -0,0 +1,249 @@
+use std::{
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{self, bail};
+use futures::future::join_all;
+use rand::Rng;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::{
+  bridge::{Bridge, MesagistoBridge},
+  chat::{echo::EchoGuard, tellraw::Tellraw},
+  config::{AccountConfig, BindingConfig, ReconnectConfig, ServerConfig, CONFIG},
+  exts::uuid::UuidExt,
+  game::{
+    disconnect::Kicked, queue, tablist::TabList, throttle::ChatThrottle, Client, Server,
+  },
+  handlers::ConnContext,
+  login::bot_user,
+};
+
+const TARGET: &str = "mesagisto::supervisor";
+
+// a connection that stayed up this long counts as healthy again
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Runs every binding in a task of its own, so a binding which gives up or
+/// panics leaves the others running. Returns once all of them have stopped.
+pub async fn run_all(
+  bindings: &'static [BindingConfig],
+  reconnect: &'static ReconnectConfig,
+) -> eyre::Result<()> {
+  let tasks = bindings.iter().map(|binding| {
+    let span = info_span!("binding", name = %binding.name);
+    tokio::spawn(
+      async move {
+        let bridge = MesagistoBridge::new(binding.name.clone(), binding.channel.clone());
+        let result = match bridge.await {
+          Ok(bridge) => run(binding, reconnect, Arc::new(bridge)).await,
+          Err(err) => Err(err),
+        };
+        if let Err(err) = &result {
+          error!(target: TARGET, "Binding {} stopped: {:?}", binding.name, err);
+        }
+      }
+      .instrument(span),
+    )
+  });
+  for (binding, result) in bindings.iter().zip(join_all(tasks).await) {
+    if let Err(err) = result {
+      error!(target: TARGET, "Binding {} panicked: {}", binding.name, err);
+    }
+  }
+  bail!("Every binding has stopped")
+}
+
+/// Keeps the bot connected to the server, reconnecting whenever the read queue
+/// or the write handler ends. The profile is fetched again before every
+/// attempt, which only costs a validation request while the cached session is
+/// good, and picks up a new one after it was invalidated.
+async fn run(
+  binding: &BindingConfig,
+  reconnect: &ReconnectConfig,
+  bridge: Arc<dyn Bridge>,
+) -> eyre::Result<()> {
+  let (server, account) = (&binding.server, &binding.account);
+  let address = &server.address;
+  let mut backoff = Backoff::new(reconnect);
+  loop {
+    match connect(server, account).await {
+      Ok((client, conn)) => {
+        info!(target: TARGET, "Connected to {}", address);
+        // only time spent serving counts, a slow login is not a healthy link
+        let connected_at = Instant::now();
+        match serve(client, conn, bridge.clone()).await {
+          Ok(()) => warn!(target: TARGET, "Connection to {} closed", address),
+          Err(err) => {
+            announce_kick(bridge.as_ref(), &err).await;
+            give_up_if_kicked(&err)?;
+            warn!(target: TARGET, "Connection to {} lost: {:?}", address, err);
+          }
+        }
+        if connected_at.elapsed() >= STABLE_AFTER {
+          backoff.reset();
+        }
+      }
+      Err(err) => {
+        announce_kick(bridge.as_ref(), &err).await;
+        give_up_if_kicked(&err)?;
+        warn!(target: TARGET, "Failed to connect to {}: {:?}", address, err);
+      }
+    }
+    if !reconnect.enable {
+      bail!("Disconnected from {} and reconnecting is disabled", address);
+    }
+    match backoff.next_delay() {
+      Some(delay) => {
+        info!(
+          target: TARGET,
+          "Reconnecting to {} in {:.1}s (attempt {})",
+          address,
+          delay.as_secs_f32(),
+          backoff.attempts
+        );
+        tokio::time::sleep(delay).await;
+      }
+      None => bail!(
+        "Giving up on {} after {} reconnect attempts",
+        address,
+        backoff.attempts - 1
+      ),
+    }
+  }
+}
+
+// tells the channel why the bot left, before it reconnects or gives up
+async fn announce_kick(bridge: &dyn Bridge, err: &eyre::Report) {
+  if let Some(kicked) = err.downcast_ref::<Kicked>() {
+    if let Err(err) = bridge.notify(kicked.to_string()).await {
+      warn!(target: TARGET, "Failed to announce the kick: {:?}", err);
+    }
+  }
+}
+
+// bans and whitelists will not go away by retrying
+fn give_up_if_kicked(err: &eyre::Report) -> eyre::Result<()> {
+  if let Some(kicked) = err.downcast_ref::<Kicked>() {
+    if !kicked.kind.should_retry() {
+      match kicked.kind.hint() {
+        Some(hint) => bail!("{}, not reconnecting: {}", kicked, hint),
+        None => bail!("{}, not reconnecting ({:?})", kicked, kicked.kind),
+      }
+    }
+  }
+  Ok(())
+}
+
+async fn connect(
+  server: &ServerConfig,
+  account: &AccountConfig,
+) -> eyre::Result<(Arc<Client>, Server)> {
+  let client = Arc::new(Client::new(server.protocol_version, bot_user(account).await?));
+  let server = client.connect_to(&server.address).await?;
+  Ok((client, server))
+}
+
+/// Runs the handlers of a single connection until either side ends. Every
+/// channel is created here, so nothing leaks from one connection to the next.
+async fn serve(
+  client: Arc<Client>,
+  mut server: Server,
+  bridge: Arc<dyn Bridge>,
+) -> eyre::Result<()> {
+  let read_rx = server.read_queue.take().unwrap();
+  let write = server.conn.take().unwrap();
+  let (write_tx, write_rx) = queue::channel("write", CONFIG.queue.write_capacity);
+  let write_metrics = write_tx.metrics();
+  let echo = Arc::new(EchoGuard::new(
+    server.uuid.to_uuid(),
+    client.profile.username.clone(),
+  ));
+
+  let throttle = Arc::new(ChatThrottle::new(&CONFIG.throttle, server.protocol_version));
+  let tellraw = Arc::new(Tellraw::new(CONFIG.tellraw.enable));
+  let tablist = Arc::new(TabList::new(
+    server.uuid.to_uuid(),
+    client.profile.username.clone(),
+  ));
+
+  let mut write_task = tokio::spawn(crate::handlers::write::handler(write, write_rx));
+  let throttle_task = tokio::spawn(throttle.clone().run(
+    write_tx.clone(),
+    echo.clone(),
+    tellraw.clone(),
+  ));
+  let remote_task = tokio::spawn(crate::handlers::remote::forward(
+    bridge.clone(),
+    server.state.clone(),
+    server.protocol_version,
+    throttle.clone(),
+    tellraw.clone(),
+    tablist.clone(),
+  ));
+  let ctx = ConnContext {
+    state: server.state.clone(),
+    write_tx,
+    bridge,
+    echo,
+    throttle,
+    tellraw,
+    tablist,
+  };
+  let mut read_task = tokio::spawn(crate::handlers::init(client, read_rx, ctx));
+
+  let result = tokio::select! {
+    result = &mut write_task => {
+      read_task.abort();
+      result
+    }
+    result = &mut read_task => {
+      write_task.abort();
+      result
+    }
+  };
+  remote_task.abort();
+  throttle_task.abort();
+  info!(
+    target: TARGET,
+    "Queue metrics of the connection: read {}, write {}", server.read_metrics, write_metrics
+  );
+  result?
+}
+
+struct Backoff {
+  initial: Duration,
+  max: Duration,
+  max_attempts: u32,
+  attempts: u32,
+}
+
+impl Backoff {
+  fn new(config: &ReconnectConfig) -> Self {
+    Self {
+      initial: Duration::from_secs(config.initial_delay),
+      max: Duration::from_secs(config.max_delay),
+      max_attempts: config.max_attempts,
+      attempts: 0,
+    }
+  }
+
+  fn reset(&mut self) {
+    self.attempts = 0;
+  }
+
+  // exponential delay with equal jitter, so bots bridging the same server do
+  // not reconnect in lockstep after it restarts
+  fn next_delay(&mut self) -> Option<Duration> {
+    self.attempts += 1;
+    if self.max_attempts != 0 && self.attempts > self.max_attempts {
+      return None;
+    }
+    let exp = self.attempts.saturating_sub(1).min(16);
+    let base = self.initial.saturating_mul(1 << exp).min(self.max);
+    let half = base / 2;
+    let jitter = rand::thread_rng().gen_range(Duration::ZERO..=half);
+    Some(half + jitter)
+  }
+}